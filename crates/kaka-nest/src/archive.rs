@@ -0,0 +1,116 @@
+use std::fs;
+
+use chrono::Datelike;
+use serde::Serialize;
+use serde_json::json;
+use tracing::{Level, debug, info, span};
+
+use crate::{
+    build_page::{Post, PostList, render_page, write_page},
+    config::Config,
+    error::{BuildError, IoResultExt},
+};
+
+/// The posts written in one month.
+#[derive(Serialize)]
+struct ArchiveMonth<'a> {
+    /// The full name of the month, eg. `August`
+    name: String,
+    number: u32,
+    posts: Vec<&'a Post>,
+}
+
+/// The posts written in one year, newest month first.
+#[derive(Serialize)]
+struct ArchiveYear<'a> {
+    year: i32,
+    /// The per-year page, only set when they are enabled
+    url: Option<String>,
+    count: usize,
+    months: Vec<ArchiveMonth<'a>>,
+}
+
+/// Writes `/archive/index.html`, listing every published post grouped by year and month,
+/// and a page for each year at `/archive/{year}/` if they are enabled.
+pub(crate) fn output_archive_to_fs(blogs: &PostList, config: &Config) -> Result<(), BuildError> {
+    let span = span!(Level::INFO, "output archive");
+    let _enter = span.enter();
+
+    info!("creating archive");
+    let archive_dir = config.paths.output.join("archive");
+    if archive_dir.exists() {
+        fs::remove_dir_all(&archive_dir).with_path(&archive_dir)?;
+    }
+
+    let years = group_posts(&blogs.blogs, config.archive.year_pages);
+
+    let ctx = json!({ "years": years, "year": None::<i32>, "site": config.site });
+    let archive_file = archive_dir.join("index.html");
+    let contents = render_page("archive", &ctx, &archive_file)?;
+    write_page(&archive_file, contents)?;
+
+    if config.archive.year_pages {
+        for year in &years {
+            debug!("writing archive for {}", year.year);
+            let ctx = json!({
+                "years": [year],
+                "year": year.year,
+                "site": config.site,
+            });
+            let year_file = archive_dir.join(year.year.to_string()).join("index.html");
+            let contents = render_page("archive", &ctx, &year_file)?;
+            write_page(&year_file, contents)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// The years which have posts, used for the per-year pages in the sitemap.
+pub(crate) fn archive_years(blogs: &PostList) -> Vec<i32> {
+    let mut years: Vec<_> = blogs
+        .blogs
+        .iter()
+        .map(|post| post.metadata.date.datetime().year())
+        .collect();
+    years.dedup();
+    years
+}
+
+/// Groups `posts`, which are sorted newest first, by the year and month they were written.
+fn group_posts(posts: &[Post], year_pages: bool) -> Vec<ArchiveYear<'_>> {
+    let mut years: Vec<ArchiveYear> = Vec::new();
+    for post in posts {
+        let date = post.metadata.date.datetime();
+
+        if years.last().is_none_or(|last| last.year != date.year()) {
+            years.push(ArchiveYear {
+                year: date.year(),
+                url: year_pages.then(|| format!("/archive/{}/", date.year())),
+                count: 0,
+                months: Vec::new(),
+            });
+        }
+        let Some(year) = years.last_mut() else {
+            continue;
+        };
+        year.count += 1;
+
+        if year
+            .months
+            .last()
+            .is_none_or(|last| last.number != date.month())
+        {
+            year.months.push(ArchiveMonth {
+                name: date.format("%B").to_string(),
+                number: date.month(),
+                posts: Vec::new(),
+            });
+        }
+        if let Some(month) = year.months.last_mut() {
+            month.posts.push(post);
+        }
+    }
+
+    years
+}
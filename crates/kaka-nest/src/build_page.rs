@@ -1,32 +1,79 @@
 use std::{
+    cmp::Reverse,
     collections::{HashMap, HashSet},
     fs::{self},
     path::{Path, PathBuf},
 };
 
+use chrono_tz::Tz;
 use fs_extra::{copy_items, dir::CopyOptions};
 use lol_html::{HtmlRewriter, Settings, element};
-use pulldown_cmark::{Options, Parser};
-use pullmark_parsers::{TL_PROCESSOR, format_blockquotes, highlight_codeblocks};
-use rss::{Category, ChannelBuilder, ItemBuilder};
+use pulldown_cmark::{Event, Options, Parser, Tag, TagEnd};
+use pullmark_parsers::{
+    Diagnostics, HighlightStyle, Languages, TL_PROCESSOR, TableOfContents, TocEntry,
+    anchor_headings, embed_images, format_blockquotes, highlight_codeblocks, resolve_wikilinks,
+};
+use rayon::{ThreadPoolBuilder, prelude::*};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
-use shared_utils::HANDLEBARS;
+use shared_utils::render_template;
 use slugify::slugify;
-use tracing::{Level, debug, info, span, trace};
-
-use crate::util::get_blog_paths;
+use tracing::{Level, debug, info, span, trace, warn};
+
+use crate::{
+    archive::output_archive_to_fs,
+    cache::{BuildCache, CachedPost, hash_bytes, hash_dir},
+    config::{Config, SiteConfig, TagSort},
+    date::PostDate,
+    error::{BuildError, BuildWarning, IoResultExt, Location},
+    feed::{output_atom_to_fs, output_json_feed_to_fs, output_rss_to_fs, rss_channel},
+    pagination::paginate,
+    related::{PostNavigation, post_navigation},
+    sitemap::{output_robots_to_fs, output_sitemap_to_fs},
+    tags::{TagLink, TagPosts, tag_levels, tag_slug, tag_url},
+    util::get_blog_paths,
+};
 
 #[derive(Debug, Serialize)]
 pub struct Post {
+    /// The markdown file this post was built from
+    #[serde(skip)]
+    pub path: PathBuf,
     pub title: String,
     pub slug: String,
     pub metadata: PostMetadata,
+    /// Words of prose in the post, code blocks arent counted
+    pub word_count: usize,
+    /// The estimated reading time, unless it was set in the front matter
+    pub read_mins: u32,
+    /// Links to the page of each tag in the front matter
+    pub tags: Vec<TagLink>,
+    /// The headings of the post, empty unless `toc: true` is set in the front matter
+    pub toc: Vec<TocEntry>,
     pub contents: String,
 }
+/// The context passed to the `blog` template.
+#[derive(Serialize)]
+struct PostPage<'a> {
+    #[serde(flatten)]
+    post: &'a Post,
+    #[serde(flatten)]
+    navigation: &'a PostNavigation<'a>,
+    site: &'a SiteConfig,
+}
+
 impl Post {
-    pub(crate) fn to_rendered_html(&self) -> Result<String, Box<dyn std::error::Error>> {
-        let rendered_string = HANDLEBARS.render("blog", self)?;
+    pub(crate) fn to_rendered_html(
+        &self,
+        site: &SiteConfig,
+        navigation: &PostNavigation,
+    ) -> Result<String, BuildError> {
+        let page = PostPage {
+            post: self,
+            navigation,
+            site,
+        };
+        let rendered_string = render_page("blog", &page, &self.path)?;
 
         // Replace local image links with /images/{{ image }}
         debug!("rewriting img links");
@@ -62,86 +109,332 @@ impl Post {
             |c: &[u8]| output.extend_from_slice(c),
         );
 
-        rewriter.write(&rendered_string.into_bytes())?;
-        rewriter.end()?;
+        let rewrite_error = |source| BuildError::Rewrite {
+            path: self.path.clone(),
+            source,
+        };
+        rewriter
+            .write(&rendered_string.into_bytes())
+            .map_err(rewrite_error)?;
+        rewriter.end().map_err(rewrite_error)?;
 
-        Ok(String::from_utf8(output)?)
+        Ok(String::from_utf8_lossy(&output).into_owned())
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+/// Renders a template, `path` is the file the page is being rendered for.
+pub(crate) fn render_page<T: Serialize>(
+    name: &str,
+    data: &T,
+    path: &Path,
+) -> Result<String, BuildError> {
+    render_template(name, data).map_err(|source| BuildError::Template {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+#[derive(Serialize, Debug)]
 pub struct PostMetadata {
-    pub date: String,
+    pub date: PostDate,
+    /// When the post was last edited, used as the lastmod date in the sitemap
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub updated: Option<PostDate>,
     pub published: bool,
     pub tags: Option<Vec<String>>,
-    pub read_mins: u32,
+    /// Overrides the estimated reading time
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub read_mins: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub description: Option<String>,
+    /// Show a table of contents at the top of the post
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub toc: bool,
+}
+
+/// The front matter as written, dates are parsed once the timezone is known.
+#[derive(Deserialize)]
+struct RawPostMetadata {
+    date: String,
+    #[serde(default)]
+    updated: Option<String>,
+    published: bool,
+    tags: Option<Vec<String>>,
+    #[serde(default)]
+    read_mins: Option<u32>,
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    toc: bool,
+}
+
+impl PostMetadata {
+    /// The date the post last changed, falling back to when it was written.
+    #[must_use]
+    pub fn lastmod(&self) -> PostDate {
+        self.updated.unwrap_or(self.date)
+    }
 }
 
 /// A struct containing all currently exisiting blogs & tags
 #[derive(Default, Debug)]
 pub struct PostList {
     pub blogs: Vec<Post>,
-    pub tags: HashMap<String, HashSet<String>>,
+    /// Every tag used by a published post, keyed by its slug
+    pub tags: HashMap<String, TagPosts>,
+}
+
+/// Options controlling a single run of [`create_blog_on_system`].
+#[derive(Debug, Default, Clone)]
+pub struct BuildOptions {
+    /// Ignore the build cache and render every post again
+    pub force: bool,
+    /// How many threads to render posts with, defaults to the number of CPU cores
+    pub jobs: Option<usize>,
 }
 
-fn build_post_from_path(path: &Path) -> Result<Post, Box<dyn std::error::Error>> {
-    let content = fs::read_to_string(path)?;
-    let metadata = parse_front_matter(&content)?;
-    let html = render_markdown_to_html(&content);
+/// A markdown file read from the vault, before it is rendered.
+struct SourcePost {
+    path: PathBuf,
+    title: String,
+    slug: String,
+    content: String,
+    metadata: PostMetadata,
+    word_count: usize,
+    read_mins: u32,
+}
 
-    let title = generate_title_from_path(path).ok_or("the file name is invalid")?;
-    Ok(Post {
+fn read_source_post(path: &Path, config: &Config) -> Result<SourcePost, BuildError> {
+    let content = fs::read_to_string(path).with_path(path)?;
+    let metadata = parse_front_matter(path, &content, config.site.timezone())?;
+    let word_count = count_words(&content);
+    let read_mins = metadata
+        .read_mins
+        .unwrap_or_else(|| config.reading.read_mins(word_count));
+    let title = generate_title_from_path(path).ok_or_else(|| BuildError::InvalidFileName {
+        path: path.to_path_buf(),
+    })?;
+
+    Ok(SourcePost {
+        path: path.to_path_buf(),
         title: title.into(), // or derive from metadata
         slug: slugify!(title),
+        content,
         metadata,
-        contents: html,
+        word_count,
+        read_mins,
     })
 }
 
-fn generate_title_from_path(path: &Path) -> Option<&str> {
+/// Counts the words of prose in a post, skipping the front matter and code blocks.
+fn count_words(content: &str) -> usize {
+    let mut skipping = false;
+    let mut words = 0;
+    for event in Parser::new_ext(content, markdown_options()) {
+        match event {
+            Event::Start(Tag::CodeBlock(_) | Tag::MetadataBlock(_)) => skipping = true,
+            Event::End(TagEnd::CodeBlock | TagEnd::MetadataBlock(_)) => skipping = false,
+            Event::Text(text) | Event::Code(text) if !skipping => {
+                words += text.split_whitespace().count();
+            }
+            _ => {}
+        }
+    }
+
+    words
+}
+
+/// Builds a post, returning the newly rendered html if it wasnt in the cache.
+///
+/// Wikilinks are resolved against `published`, the slug of every published post.
+fn build_post(
+    source: SourcePost,
+    published: &HashSet<String>,
+    highlight: &HighlightStyle,
+    languages: &Languages,
+    cache: &BuildCache,
+) -> Result<(Post, Vec<BuildWarning>, Option<CachedPost>), BuildError> {
+    let SourcePost {
+        path,
+        title,
+        slug,
+        content,
+        metadata,
+        word_count,
+        read_mins,
+    } = source;
+
+    let hash = hash_bytes(content.as_bytes());
+    let (html, toc, warnings, rendered) = if let Some(cached) = cache.get_post(&path, &hash) {
+        trace!("using cached html");
+        let CachedPost {
+            contents,
+            toc,
+            warnings,
+            ..
+        } = cached.clone();
+        (contents, toc, warnings, None)
+    } else {
+        let (html, toc, warnings) =
+            render_markdown_to_html(&path, &content, published, highlight, languages)?;
+        let rendered = CachedPost {
+            hash,
+            contents: html.clone(),
+            toc: toc.clone(),
+            warnings: warnings.clone(),
+        };
+        (html, toc, warnings, Some(rendered))
+    };
+
+    let tags = metadata
+        .tags
+        .iter()
+        .flatten()
+        .map(|tag| TagLink::new(tag))
+        .collect();
+    let toc = if metadata.toc { toc } else { Vec::new() };
+    let post = Post {
+        path,
+        title,
+        slug,
+        tags,
+        toc,
+        metadata,
+        word_count,
+        read_mins,
+        contents: html,
+    };
+
+    Ok((post, warnings, rendered))
+}
+
+pub(crate) fn generate_title_from_path(path: &Path) -> Option<&str> {
     let file_name = path.file_stem()?.to_str()?;
 
     Some(file_name)
 }
 
-fn render_markdown_to_html(content: &str) -> String {
-    let span = span!(Level::INFO, "pullmark parsing");
-    let _enter = span.enter();
-
+/// The markdown extensions enabled for every post.
+pub(crate) fn markdown_options() -> Options {
     let mut pullmark_options = Options::empty();
     pullmark_options.insert(Options::ENABLE_WIKILINKS);
     pullmark_options.insert(Options::ENABLE_STRIKETHROUGH);
     pullmark_options.insert(Options::ENABLE_YAML_STYLE_METADATA_BLOCKS);
     pullmark_options.insert(Options::ENABLE_TASKLISTS);
     pullmark_options.insert(Options::ENABLE_TABLES);
+    pullmark_options
+}
+
+fn render_markdown_to_html(
+    path: &Path,
+    content: &str,
+    published: &HashSet<String>,
+    highlight: &HighlightStyle,
+    languages: &Languages,
+) -> Result<(String, Vec<TocEntry>, Vec<BuildWarning>), BuildError> {
+    let span = span!(Level::INFO, "pullmark parsing");
+    let _enter = span.enter();
+
+    let pullmark_options = markdown_options();
+    let diagnostics = Diagnostics::default();
+    let toc = TableOfContents::default();
 
     let html_output = TL_PROCESSOR.with_borrow_mut(|processer| {
         debug!("created parser");
-        let parser = Parser::new_ext(content, pullmark_options);
+        let parser = Parser::new_ext(content, pullmark_options).into_offset_iter();
+        debug!("resolving wikilinks");
+        let parser = resolve_wikilinks(parser, published, &diagnostics);
+        debug!("embedding images");
+        let parser = embed_images(parser);
+        debug!("anchoring headings");
+        let parser = anchor_headings(parser, &toc);
         debug!("highlighting codeblocks");
-        let parser = highlight_codeblocks(parser, processer);
+        let parser = highlight_codeblocks(parser, processer, highlight, languages, &diagnostics);
         debug!("formatting blockquotes");
-        let parser = format_blockquotes(parser);
+        let parser = format_blockquotes(parser, &diagnostics);
 
         let mut html_output = String::new();
-        pulldown_cmark::html::push_html(&mut html_output, parser);
+        pulldown_cmark::html::push_html(&mut html_output, parser.map(|(event, _)| event));
 
         html_output
     });
 
+    let (errors, warnings) = diagnostics.into_parts();
+    if let Some(source) = errors.into_iter().next() {
+        return Err(BuildError::Markdown {
+            path: path.to_path_buf(),
+            location: Location::from_offset(content, source.offset()),
+            source,
+        });
+    }
+
+    let warnings = warnings
+        .into_iter()
+        .map(|warning| BuildWarning {
+            path: path.to_path_buf(),
+            location: Location::from_offset(content, warning.offset()),
+            message: warning.to_string(),
+        })
+        .collect();
+
     debug!("finished parsing into html");
-    html_output
+    Ok((html_output, toc.into_entries(), warnings))
 }
 
-fn parse_front_matter(content: &str) -> Result<PostMetadata, Box<dyn std::error::Error>> {
+/// Returns the yaml between the first pair of `---`, and the byte offset it starts at.
+pub(crate) fn split_front_matter(content: &str) -> Option<(usize, &str)> {
+    let start = content.find("---")? + 3;
+    let len = content[start..].find("---")?;
+
+    Some((start, &content[start..start + len]))
+}
+
+/// Parses the front matter of a post, dates without an offset are in `timezone`.
+pub(crate) fn parse_front_matter(
+    path: &Path,
+    content: &str,
+    timezone: Tz,
+) -> Result<PostMetadata, BuildError> {
     debug!("extracting metadata from file");
 
-    let Some(blog_metadata_string) = content.split("---").nth(1) else {
-        return Err("Didnt include metadata for file".into());
+    let Some((start, blog_metadata_string)) = split_front_matter(content) else {
+        return Err(BuildError::FrontMatter {
+            path: path.to_path_buf(),
+            location: None,
+            reason: "Didnt include metadata for file".into(),
+        });
     };
 
-    let mut blog_metadata: PostMetadata = serde_yaml::from_str(blog_metadata_string)?;
+    let raw: RawPostMetadata =
+        serde_yaml::from_str(blog_metadata_string).map_err(|err| BuildError::FrontMatter {
+            path: path.to_path_buf(),
+            // Yaml line numbers start on the line containing the opening `---`
+            location: err.location().map(|location| Location {
+                line: Location::from_offset(content, start).line + location.line() - 1,
+                column: location.column(),
+            }),
+            reason: err.to_string(),
+        })?;
+
+    let parse_date = |key: &str, date: &str| {
+        PostDate::parse(date, timezone).map_err(|reason| BuildError::FrontMatter {
+            path: path.to_path_buf(),
+            location: front_matter_key_location(content, start, key),
+            reason,
+        })
+    };
+    let mut blog_metadata = PostMetadata {
+        date: parse_date("date", &raw.date)?,
+        updated: raw
+            .updated
+            .map(|updated| parse_date("updated", &updated))
+            .transpose()?,
+        published: raw.published,
+        tags: raw.tags,
+        read_mins: raw.read_mins,
+        description: raw.description,
+        toc: raw.toc,
+    };
 
     // Remove '#' prefix from each tag if present
     if let Some(tags) = &mut blog_metadata.tags {
@@ -155,208 +448,441 @@ fn parse_front_matter(content: &str) -> Result<PostMetadata, Box<dyn std::error:
     Ok(blog_metadata)
 }
 
-fn build_blog_list(blog_paths: &[PathBuf]) -> Result<PostList, Box<dyn std::error::Error>> {
+/// Finds the value of `key` in the front matter starting at `start`.
+fn front_matter_key_location(content: &str, start: usize, key: &str) -> Option<Location> {
+    let mut offset = start;
+    for line in content[start..].split_inclusive('\n') {
+        if let Some(value) = line
+            .strip_prefix(key)
+            .and_then(|rest| rest.strip_prefix(':'))
+        {
+            let value_start = offset + line.len() - value.trim_start().len();
+            return Some(Location::from_offset(content, value_start));
+        }
+        offset += line.len();
+    }
+    None
+}
+
+fn build_blog_list(
+    sources: Vec<SourcePost>,
+    published: &HashSet<String>,
+    highlight: &HighlightStyle,
+    languages: &Languages,
+    cache: &mut BuildCache,
+) -> Result<(PostList, Vec<BuildWarning>), BuildError> {
     let mut blog_list = PostList::default();
+    let mut warnings = Vec::new();
+
+    // Posts are rendered in parallel, collecting keeps them in the same order as `sources`.
+    // Unpublished posts are never written, so they arent rendered or warned about
+    let built: Vec<_> = sources
+        .into_par_iter()
+        .filter(|source| source.metadata.published)
+        .map(|source| build_post(source, published, highlight, languages, cache))
+        .collect::<Result<_, _>>()?;
+
+    for (blog, post_warnings, rendered) in built {
+        warnings.extend(post_warnings);
+        if let Some(rendered) = rendered {
+            cache.insert_post(blog.path.clone(), rendered);
+        }
 
-    for path in blog_paths {
-        let blog = build_post_from_path(path)?;
-        if let Some(tags) = &blog.metadata.tags {
-            for tag in tags {
+        // Posts are listed on the page of their tag and every parent of it
+        for tag in blog.metadata.tags.iter().flatten() {
+            for level in tag_levels(tag) {
                 blog_list
                     .tags
-                    .entry(tag.clone())
-                    .or_default()
+                    .entry(tag_slug(level))
+                    .or_insert_with(|| TagPosts {
+                        name: level.to_string(),
+                        ..TagPosts::default()
+                    })
+                    .posts
                     .insert(blog.slug.clone());
             }
         }
 
-        if !blog.metadata.published {
-            continue;
-        }
-
         blog_list.blogs.push(blog);
     }
 
-    Ok(blog_list)
+    // Newest first, this order is used by the homepage, tag pages and feeds
+    blog_list
+        .blogs
+        .sort_by_key(|post| Reverse(post.metadata.date));
+
+    Ok((blog_list, warnings))
 }
 
 /// This function reads all input files from the operating systemm,
 /// builds the blogs and Copys the images and static assets to output directory
 ///
+/// Only posts which changed since the last build are rendered again, unless
+/// the templates changed or [`BuildOptions::force`] is set.
+///
+/// Returns every warning raised while building, these have already been logged.
+///
 /// # Errors
 /// - Deleting output directory
 /// - Copying assets
 /// - Converting `OsStr` to string
 /// - Reading input dir
-pub fn create_blog_on_system() -> Result<(), Box<dyn std::error::Error>> {
-    let blog_paths = get_blog_paths()?;
-    let posts = build_blog_list(&blog_paths)?;
-
-    trace!("Deleting output directory");
-    if Path::new("./output").exists() {
-        fs::remove_dir_all("./output")?;
+/// - Writing the build cache
+/// - Creating the worker thread pool
+pub fn create_blog_on_system(
+    config: &Config,
+    options: &BuildOptions,
+) -> Result<Vec<BuildWarning>, BuildError> {
+    let mut pool = ThreadPoolBuilder::new();
+    if let Some(jobs) = options.jobs {
+        pool = pool.num_threads(jobs);
     }
-    // Create the base output directory first, as threads will try to write to it.
-    fs::create_dir("./output")?;
+    pool.build()?.install(|| build_site(config, options))
+}
 
-    // --- Start of Copying ---
-    info!("Copying static files and images");
-    let static_src = "./assets/static";
-    let images_src = "./assets/blog/images";
-    let static_dest = "./output";
-    let images_dest = "./output/images";
+fn build_site(config: &Config, options: &BuildOptions) -> Result<Vec<BuildWarning>, BuildError> {
+    let output = &config.paths.output;
 
-    // -- Copy Static Files --
-    let mut options = CopyOptions::new();
-    options.overwrite = true;
-    let paths_to_copy: Vec<_> = fs::read_dir(static_src)?
-        .filter_map(Result::ok) // Ignore any read errors for individual entries
-        .map(|entry| entry.path())
+    let mut cache = if options.force {
+        BuildCache::default()
+    } else {
+        BuildCache::load(&config.paths.cache)
+    };
+    let blog_paths = get_blog_paths(&config.paths.content).with_path(&config.paths.content)?;
+    let sources: Vec<_> = blog_paths
+        .par_iter()
+        .map(|path| read_source_post(path, config))
+        .collect::<Result<_, _>>()?;
+
+    // Every post has to be read before rendering, so wikilinks know which posts exist
+    let published: HashSet<_> = sources
+        .iter()
+        .filter(|source| source.metadata.published)
+        .map(|source| source.slug.clone())
         .collect();
-    copy_items(&paths_to_copy, static_dest, &options)?;
-
-    // -- Copy image files --
-    fs::create_dir_all(images_dest)?;
-    let paths_to_copy: Vec<_> = fs::read_dir(images_src)?
-        .filter_map(Result::ok) // Ignore any read errors for individual entries
-        .map(|entry| entry.path())
+    let mut published_slugs: Vec<_> = published.iter().map(String::as_str).collect();
+    published_slugs.sort_unstable();
+
+    let templates_hash = hash_dir(&config.paths.templates).with_path(&config.paths.templates)?;
+    // Every page depends on some of the config, so any change to it rebuilds everything
+    let config_hash = hash_bytes(format!("{config:?}").as_bytes());
+    let published_hash = hash_bytes(published_slugs.join("\n").as_bytes());
+    let full_rebuild = cache.invalidate_if_stale(&templates_hash, &config_hash, &published_hash)
+        || !output.exists();
+
+    let published_paths: Vec<_> = sources
+        .iter()
+        .filter(|source| source.metadata.published)
+        .map(|source| source.path.clone())
         .collect();
-    copy_items(&paths_to_copy, images_dest, &options)?;
-    info!("Finished copying blog images");
+    cache.retain_posts(&published_paths);
+    let (posts, warnings) = build_blog_list(
+        sources,
+        &published,
+        &config.highlight.style(),
+        &config.highlight.languages(),
+        &mut cache,
+    )?;
+    for warning in &warnings {
+        warn!("{warning}");
+    }
+    let index_changed = cache.update_index();
 
-    // --- End of Copying ---
-    info!("All file copying complete.");
+    // The output is about to be changed, if the build fails part way through the cache
+    // would no longer describe it, so it is only written back once everything succeeds
+    BuildCache::discard(&config.paths.cache).with_path(&config.paths.cache)?;
+
+    if full_rebuild {
+        trace!("Deleting output directory");
+        if output.exists() {
+            fs::remove_dir_all(output).with_path(output)?;
+        }
+    }
+    // Create the base output directory first, as threads will try to write to it.
+    fs::create_dir_all(output).with_path(output)?;
+
+    copy_static_files(config)?;
 
     // Create remaining output directories
-    fs::create_dir_all("./output/posts")?;
-    fs::create_dir_all("./output/tags")?;
+    let posts_dir = output.join("posts");
+    fs::create_dir_all(&posts_dir).with_path(&posts_dir)?;
+    remove_stale_posts(&posts, &posts_dir)?;
 
     // Output all generated files.
     {
         let span = span!(Level::INFO, "output generated files");
         let _enter = span.enter();
 
-        for blog in &posts.blogs {
-            let span = tracing::span!(Level::INFO, "render blog", blog = blog.title);
-            let _enter = span.enter();
+        // Links to other posts change whenever any post does
+        let navigation = post_navigation(&posts.blogs, config.related.count);
+        let links_changed = full_rebuild || index_changed;
 
-            info!("converting to html");
-            let blog_html = blog.to_rendered_html()?;
-
-            debug!("writing to filesytem");
-            fs::create_dir(format!("./output/posts/{}", blog.slug))?;
-            fs::write(
-                format!("./output/posts/{}/index.html", blog.slug),
-                blog_html,
-            )?;
+        posts
+            .blogs
+            .par_iter()
+            .zip(&navigation)
+            .try_for_each(|(blog, navigation)| {
+                let span = tracing::span!(Level::INFO, "render blog", blog = blog.title);
+                let _enter = span.enter();
+
+                let blog_dir = posts_dir.join(&blog.slug);
+                let blog_file = blog_dir.join("index.html");
+                if !links_changed && !cache.is_dirty(&blog.path) && blog_file.exists() {
+                    debug!("unchanged, skipping");
+                    return Ok(());
+                }
+
+                info!("converting to html");
+                let blog_html = blog.to_rendered_html(&config.site, navigation)?;
+
+                debug!("writing to filesytem");
+                fs::create_dir_all(&blog_dir).with_path(&blog_dir)?;
+                fs::write(&blog_file, blog_html).with_path(&blog_file)
+            })?;
+
+        if links_changed {
+            output_listings_to_fs(&posts, config)?;
+        } else {
+            info!("no posts changed, skipping homepage, tags, archive and feeds");
         }
+        // Only depend on the config and are cheap to write, so they are always regenerated
+        output_robots_to_fs(config)?;
+        output_highlight_css_to_fs(config)?;
+    }
+
+    cache
+        .save(&config.paths.cache)
+        .with_path(&config.paths.cache)?;
+
+    Ok(warnings)
+}
+
+/// Deletes the output of posts which were removed or unpublished since the last build.
+fn remove_stale_posts(posts: &PostList, posts_dir: &Path) -> Result<(), BuildError> {
+    let published: HashSet<_> = posts.blogs.iter().map(|blog| blog.slug.as_str()).collect();
 
-        output_tags_to_fs(&posts)?;
-        output_homepage_to_fs(&posts)?;
-        output_rss_to_fs(&posts)?;
+    for entry in fs::read_dir(posts_dir).with_path(posts_dir)? {
+        let path = entry.with_path(posts_dir)?.path();
+        let is_published = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| published.contains(name));
+
+        if !is_published {
+            debug!("removing stale post {}", path.display());
+            fs::remove_dir_all(&path).with_path(&path)?;
+        }
     }
 
     Ok(())
 }
 
-fn output_rss_to_fs(blogs: &PostList) -> Result<(), Box<dyn std::error::Error>> {
-    let span = span!(Level::INFO, "output rss");
-    let _enter = span.enter();
+/// Copies the static files and the images from the vault into the output directory.
+fn copy_static_files(config: &Config) -> Result<(), BuildError> {
+    let output = &config.paths.output;
 
-    info!("creating channel");
-    let mut channel = ChannelBuilder::default()
-        .title("Squawkykaka")
-        .link("https://squawkykaka.com")
-        .description("The RSS Feed for squawykaka.com")
-        .build();
-
-    // TODO fix all the .clones here
-    for post in &blogs.blogs {
-        let span = span!(Level::INFO, "post catagories", post = post.title);
-        let _enter = span.enter();
+    // --- Start of Copying ---
+    info!("Copying static files and images");
+    let static_src = &config.paths.static_files;
+    let images_src = config.paths.images();
+    let static_dest = output;
+    let images_dest = output.join("images");
 
-        let catagories: Vec<Category> = match post.metadata.tags.as_ref() {
-            Some(tags) => tags
-                .iter()
-                .map(|name| Category {
-                    name: name.clone(),
-                    domain: None,
-                })
-                .collect(),
-            None => vec![],
-        };
+    // -- Copy Static Files --
+    let mut options = CopyOptions::new();
+    options.overwrite = true;
+    let paths_to_copy: Vec<_> = fs::read_dir(static_src)
+        .with_path(static_src)?
+        .filter_map(Result::ok) // Ignore any read errors for individual entries
+        .map(|entry| entry.path())
+        .collect();
+    copy_items(&paths_to_copy, static_dest, &options).map_err(|source| BuildError::Copy {
+        path: static_src.clone(),
+        source,
+    })?;
 
-        let rss_post = ItemBuilder::default()
-            .title(post.title.clone())
-            .author(String::from("squawkykaka@gmail.com"))
-            .categories(catagories)
-            .pub_date(post.metadata.date.clone())
-            .content(post.contents.clone())
-            .link(format!(
-                "https://squawkykaka.com/posts/{}/index.html",
-                post.slug
-            ))
-            .build();
-
-        info!("finished post");
-
-        channel.items.push(rss_post);
-    }
+    // -- Copy image files --
+    fs::create_dir_all(&images_dest).with_path(&images_dest)?;
+    let paths_to_copy: Vec<_> = fs::read_dir(&images_src)
+        .with_path(&images_src)?
+        .filter_map(Result::ok) // Ignore any read errors for individual entries
+        .map(|entry| entry.path())
+        .collect();
+    copy_items(&paths_to_copy, &images_dest, &options).map_err(|source| BuildError::Copy {
+        path: images_src.clone(),
+        source,
+    })?;
+    info!("Finished copying blog images");
 
-    debug!("writing rss to fs");
-    fs::write("./output/index.xml", channel.to_string())?;
+    // --- End of Copying ---
+    info!("All file copying complete.");
 
     Ok(())
-    // todo!()
 }
 
-fn output_tags_to_fs(blogs: &PostList) -> Result<(), Box<dyn std::error::Error>> {
+/// Writes `/highlight.css`, the colors of highlighted code.
+fn output_highlight_css_to_fs(config: &Config) -> Result<(), BuildError> {
+    info!("creating highlight stylesheet");
+    let css_file = config.paths.output.join("highlight.css");
+    fs::write(&css_file, config.highlight.stylesheet()).with_path(&css_file)
+}
+
+/// Writes every page which lists posts, these only change when a post does.
+fn output_listings_to_fs(posts: &PostList, config: &Config) -> Result<(), BuildError> {
+    output_tags_to_fs(posts, config)?;
+    output_homepage_to_fs(posts, config)?;
+    output_archive_to_fs(posts, config)?;
+    output_rss_to_fs(posts, config)?;
+    output_atom_to_fs(posts, config)?;
+    output_json_feed_to_fs(posts, config)?;
+    output_sitemap_to_fs(posts, config)
+}
+
+fn output_tags_to_fs(blogs: &PostList, config: &Config) -> Result<(), BuildError> {
     let span = span!(Level::DEBUG, "output tags");
     let _enter = span.enter();
 
     info!("outputting tags");
-    for (tag, blogs_with) in &blogs.tags {
-        let span = span!(Level::DEBUG, "tag", name = tag);
+    let tags_root = config.paths.output.join("tags");
+    if tags_root.exists() {
+        fs::remove_dir_all(&tags_root).with_path(&tags_root)?;
+    }
+    fs::create_dir_all(&tags_root).with_path(&tags_root)?;
+
+    for (slug, tag) in &blogs.tags {
+        let span = span!(Level::DEBUG, "tag", name = tag.name);
         let _enter = span.enter();
 
         debug!("filtering posts");
         let posts: Vec<_> = blogs
             .blogs
             .iter()
-            .filter(|blog| blogs_with.contains(&blog.slug))
+            .filter(|blog| tag.posts.contains(&blog.slug))
             .collect();
 
-        let json_tag = json!({
-            "name": tag,
-            "posts": posts
-        });
+        let levels = tag_levels(&tag.name);
+        let breadcrumbs: Vec<_> = levels[..levels.len() - 1]
+            .iter()
+            .map(|level| TagLink {
+                // Each crumb only shows its own level, `homelab/proxmox` is shown as `proxmox`
+                name: level.rsplit('/').next().unwrap_or(level).to_string(),
+                ..TagLink::new(level)
+            })
+            .collect();
+        let mut children: Vec<_> = blogs
+            .tags
+            .iter()
+            .filter(|(child, _)| {
+                child
+                    .strip_prefix(slug.as_str())
+                    .and_then(|rest| rest.strip_prefix('/'))
+                    .is_some_and(|rest| !rest.contains('/'))
+            })
+            .map(|(_, child)| TagLink::new(&child.name))
+            .collect();
+        children.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let tag_dir = tags_root.join(slug);
+        let url = tag_url(slug);
+        for page in paginate(&posts, config.pagination.tag_posts_per_page, &url) {
+            let json_tag = json!({
+                "name": tag.name,
+                "breadcrumbs": breadcrumbs,
+                "children": children,
+                "posts": page.items,
+                "pagination": page.pagination,
+                "feed": format!("{url}index.xml"),
+                "site": config.site,
+            });
+
+            let tag_file = tag_dir.join(&page.file);
+            let contents = render_page("tag_page", &json_tag, &tag_file)?;
+
+            debug!("writing page {} to fs", page.pagination.current);
+            write_page(&tag_file, contents)?;
+        }
 
-        let contents = HANDLEBARS.render("tag_page", &json_tag)?;
+        debug!("writing tag feed to fs");
+        let site = &config.site;
+        let channel = rss_channel(
+            format!("#{} - {}", tag.name, site.title),
+            format!("{}{url}", site.base_url),
+            format!("Posts tagged #{} on {}", tag.name, site.title),
+            posts,
+            config,
+        );
+        let feed_file = tag_dir.join("index.xml");
+        fs::write(&feed_file, channel.to_string()).with_path(&feed_file)?;
+    }
 
-        trace!("stripping # prefix");
-        let stripped_tag = match tag.strip_prefix("#") {
-            Some(tag) => tag,
-            None => tag.as_str(),
-        };
+    output_tag_index_to_fs(blogs, config)
+}
 
-        debug!("writing to fs");
-        fs::create_dir(format!("./output/tags/{stripped_tag}"))?;
-        fs::write(format!("./output/tags/{stripped_tag}/index.html"), contents)?;
+/// A tag listed on the tag index.
+#[derive(Serialize)]
+struct TagSummary<'a> {
+    name: &'a str,
+    url: String,
+    count: usize,
+}
+
+/// Writes `/tags/index.html`, listing every tag with how many posts use it.
+fn output_tag_index_to_fs(blogs: &PostList, config: &Config) -> Result<(), BuildError> {
+    info!("outputting tag index");
+
+    let mut tags: Vec<_> = blogs
+        .tags
+        .iter()
+        .map(|(slug, tag)| TagSummary {
+            name: &tag.name,
+            url: tag_url(slug),
+            count: tag.posts.len(),
+        })
+        .collect();
+    match config.tags.sort {
+        TagSort::Count => tags.sort_by(|a, b| b.count.cmp(&a.count).then(a.name.cmp(b.name))),
+        TagSort::Name => tags.sort_by(|a, b| a.name.cmp(b.name)),
     }
 
-    Ok(())
+    let ctx = json!({ "tags": tags, "site": config.site });
+    let index_file = config.paths.output.join("tags").join("index.html");
+    let contents = render_page("tag_index", &ctx, &index_file)?;
+
+    write_page(&index_file, contents)
 }
 
-fn output_homepage_to_fs(blogs: &PostList) -> Result<(), Box<dyn std::error::Error>> {
+fn output_homepage_to_fs(blogs: &PostList, config: &Config) -> Result<(), BuildError> {
     let span = span!(Level::DEBUG, "output homepage");
     let _enter = span.enter();
     info!("outputting homepage");
 
-    // The homepage template expects an object with a `blogs` field
-    let ctx = json!({ "blogs": blogs.blogs });
-    let contents = HANDLEBARS.render("homepage", &ctx)?;
+    // Pages are numbered from the newest post, so old pages have to be removed
+    let pages_dir = config.paths.output.join("page");
+    if pages_dir.exists() {
+        fs::remove_dir_all(&pages_dir).with_path(&pages_dir)?;
+    }
 
-    fs::write("./output/index.html", contents)?;
+    for page in paginate(&blogs.blogs, config.pagination.posts_per_page, "/") {
+        // The homepage template expects an object with a `blogs` field
+        let ctx = json!({
+            "blogs": page.items,
+            "pagination": page.pagination,
+            "site": config.site,
+        });
+        let homepage_file = config.paths.output.join(&page.file);
+        let contents = render_page("homepage", &ctx, &homepage_file)?;
+
+        write_page(&homepage_file, contents)?;
+    }
 
     Ok(())
 }
+
+/// Writes a rendered page, creating the directory it is in.
+pub(crate) fn write_page(file: &Path, contents: String) -> Result<(), BuildError> {
+    if let Some(dir) = file.parent() {
+        fs::create_dir_all(dir).with_path(dir)?;
+    }
+
+    fs::write(file, contents).with_path(file)
+}
@@ -0,0 +1,175 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use pullmark_parsers::TocEntry;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, warn};
+
+use crate::{error::BuildWarning, util::visit_dir};
+
+/// Bumping the tool version invalidates every cached post.
+const TOOL_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// A post which was rendered in a previous build.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CachedPost {
+    /// Hash of the markdown file, including its front matter
+    pub hash: String,
+    pub contents: String,
+    #[serde(default)]
+    pub toc: Vec<TocEntry>,
+    /// Warnings raised while rendering, so they are reported again on later builds
+    #[serde(default)]
+    pub warnings: Vec<BuildWarning>,
+}
+
+/// The on-disk manifest used for incremental builds.
+///
+/// Posts are keyed by the hash of their markdown file, the whole cache is thrown away when
+/// the templates, the config, the set of published posts or the tool version change.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct BuildCache {
+    version: String,
+    templates: String,
+    /// Hash of the config, which every page depends on
+    #[serde(default)]
+    config: String,
+    /// Hash of the slug of every published post, which wikilinks are resolved against
+    #[serde(default)]
+    published: String,
+    posts: HashMap<PathBuf, CachedPost>,
+    /// Hash of every post, used to decide if the homepage, tags and feeds need regenerating.
+    index: Option<String>,
+
+    /// Posts which were (re)rendered during this build.
+    #[serde(skip)]
+    dirty: HashSet<PathBuf>,
+}
+
+impl BuildCache {
+    /// Reads the cache from `path`, an unreadable cache is treated as empty.
+    pub fn load(path: &Path) -> Self {
+        let Ok(contents) = fs::read_to_string(path) else {
+            debug!("no build cache at {}", path.display());
+            return Self::default();
+        };
+
+        serde_json::from_str(&contents).unwrap_or_else(|err| {
+            warn!("ignoring corrupt build cache {}: {err}", path.display());
+            Self::default()
+        })
+    }
+
+    /// Writes the cache to `path`, creating its parent directory.
+    ///
+    /// # Errors
+    /// - Creating the cache directory
+    /// - Writing the cache file
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        fs::write(path, serde_json::to_string(self)?)
+    }
+
+    /// Deletes the cache file at `path`, so the next build starts from scratch.
+    ///
+    /// # Errors
+    /// - Removing the cache file, a missing file isnt an error
+    pub fn discard(path: &Path) -> io::Result<()> {
+        match fs::remove_file(path) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+            _ => Ok(()),
+        }
+    }
+
+    /// Clears the cache if it was made by another tool version, with different templates,
+    /// a different config or with different posts published.
+    ///
+    /// Returns true if everything has to be rebuilt.
+    pub fn invalidate_if_stale(
+        &mut self,
+        templates_hash: &str,
+        config_hash: &str,
+        published_hash: &str,
+    ) -> bool {
+        if self.version == TOOL_VERSION
+            && self.templates == templates_hash
+            && self.config == config_hash
+            && self.published == published_hash
+        {
+            return false;
+        }
+
+        info!("templates, config, published posts or tool version changed, rebuilding everything");
+        *self = Self {
+            version: TOOL_VERSION.into(),
+            templates: templates_hash.into(),
+            config: config_hash.into(),
+            published: published_hash.into(),
+            ..Self::default()
+        };
+
+        true
+    }
+
+    pub fn get_post(&self, path: &Path, hash: &str) -> Option<&CachedPost> {
+        self.posts.get(path).filter(|post| post.hash == hash)
+    }
+
+    pub fn insert_post(&mut self, path: PathBuf, post: CachedPost) {
+        self.dirty.insert(path.clone());
+        self.posts.insert(path, post);
+    }
+
+    pub fn is_dirty(&self, path: &Path) -> bool {
+        self.dirty.contains(path)
+    }
+
+    /// Forgets every post not in `paths`, used when a file is deleted from the vault or unpublished.
+    pub fn retain_posts(&mut self, paths: &[PathBuf]) {
+        self.posts.retain(|path, _| paths.contains(path));
+    }
+
+    /// Stores the hash of every post, returning true if it differs from the previous build.
+    pub fn update_index(&mut self) -> bool {
+        let mut posts: Vec<_> = self.posts.iter().collect();
+        posts.sort_by_key(|(path, _)| *path);
+
+        let mut hasher = blake3::Hasher::new();
+        for (path, post) in posts {
+            hasher.update(path.as_os_str().as_encoded_bytes());
+            hasher.update(post.hash.as_bytes());
+        }
+        let index = hasher.finalize().to_hex().to_string();
+
+        let changed = self.index.as_ref() != Some(&index);
+        self.index = Some(index);
+        changed
+    }
+}
+
+pub fn hash_bytes(bytes: &[u8]) -> String {
+    blake3::hash(bytes).to_hex().to_string()
+}
+
+/// Hashes the path and contents of every file in `dir`.
+///
+/// # Errors
+/// - Reading the directory or any file in it
+pub fn hash_dir(dir: &Path) -> io::Result<String> {
+    let mut files = visit_dir(dir)?;
+    files.sort();
+
+    let mut hasher = blake3::Hasher::new();
+    for file in files {
+        hasher.update(file.as_os_str().as_encoded_bytes());
+        hasher.update(&fs::read(&file)?);
+    }
+
+    Ok(hasher.finalize().to_hex().to_string())
+}
@@ -0,0 +1,279 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use chrono_tz::Tz;
+use color_eyre::eyre::Result;
+use percent_encoding::percent_decode_str;
+use pulldown_cmark::{CodeBlockKind, Event, Parser, Tag};
+use pullmark_parsers::{Diagnostics, FenceInfo, Languages, is_known_callout, resolve_wikilinks};
+use slugify::slugify;
+use tracing::{Level, debug, span};
+
+use crate::{
+    build_page::{
+        generate_title_from_path, markdown_options, parse_front_matter, split_front_matter,
+    },
+    config::Config,
+    error::BuildError,
+    util::{get_blog_paths, line_of},
+};
+
+/// A single problem found in the vault.
+#[derive(Debug)]
+pub struct Problem {
+    pub path: PathBuf,
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.path.display(), self.line, self.message)
+    }
+}
+
+/// How many codeblocks across the vault are written in a language.
+#[derive(Debug)]
+pub struct LanguageUsage {
+    /// The parser name if it can be highlighted, otherwise the name as written
+    pub name: String,
+    pub count: usize,
+    pub highlighted: bool,
+}
+
+impl fmt::Display for LanguageUsage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.highlighted {
+            write!(f, "{} ({})", self.name, self.count)
+        } else {
+            write!(f, "{} ({}, not highlighted)", self.name, self.count)
+        }
+    }
+}
+
+/// Everything found by [`check_vault`].
+#[derive(Debug, Default)]
+pub struct VaultReport {
+    pub problems: Vec<Problem>,
+    /// The codeblock languages used in the vault, most used first
+    pub languages: Vec<LanguageUsage>,
+}
+
+/// A post read from the vault, kept around so links between posts can be checked.
+struct VaultPost {
+    path: PathBuf,
+    content: String,
+    title: String,
+    slug: String,
+}
+
+/// Parses every post in the vault without writing any output, returning every problem found
+/// and the codeblock languages in use.
+///
+/// # Errors
+/// - Reading the vault directory
+/// - Reading a post
+pub fn check_vault(config: &Config) -> Result<VaultReport> {
+    let mut problems = Vec::new();
+    let mut language_counts = HashMap::new();
+    let mut posts = Vec::new();
+
+    for path in get_blog_paths(&config.paths.content)? {
+        let content = fs::read_to_string(&path)?;
+        let Some(title) = generate_title_from_path(&path).map(str::to_string) else {
+            problems.push(problem(&path, 1, "the file name is invalid"));
+            continue;
+        };
+
+        posts.push(VaultPost {
+            slug: slugify!(&title),
+            title,
+            path,
+            content,
+        });
+    }
+
+    let mut slugs: HashMap<&str, Vec<&Path>> = HashMap::new();
+    for post in &posts {
+        slugs.entry(&post.slug).or_default().push(&post.path);
+    }
+    for (slug, paths) in &slugs {
+        if paths.len() > 1 {
+            for path in paths {
+                problems.push(problem(path, 1, format!("duplicate slug `{slug}`")));
+            }
+        }
+    }
+
+    // Wikilinks are resolved against the published posts, the same as in a build
+    let mut published = HashSet::new();
+    for post in &posts {
+        let span = span!(Level::DEBUG, "check front matter", post = post.title);
+        let _enter = span.enter();
+
+        if check_front_matter(post, config.site.timezone(), &mut problems) {
+            published.insert(post.slug.clone());
+        }
+    }
+
+    let images = config.paths.images();
+    let languages = config.highlight.languages();
+    for post in &posts {
+        let span = span!(Level::DEBUG, "check post", post = post.title);
+        let _enter = span.enter();
+
+        check_markdown(
+            post,
+            &published,
+            &images,
+            &languages,
+            &mut language_counts,
+            &mut problems,
+        );
+    }
+
+    problems.sort_by(|a, b| a.path.cmp(&b.path).then(a.line.cmp(&b.line)));
+
+    let mut language_usage: Vec<_> = language_counts
+        .into_iter()
+        .map(|(name, count)| LanguageUsage {
+            highlighted: languages.is_known(&name),
+            name,
+            count,
+        })
+        .collect();
+    language_usage.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.name.cmp(&b.name)));
+
+    Ok(VaultReport {
+        problems,
+        languages: language_usage,
+    })
+}
+
+fn problem(path: &Path, line: usize, message: impl Into<String>) -> Problem {
+    Problem {
+        path: path.to_path_buf(),
+        line,
+        message: message.into(),
+    }
+}
+
+/// Returns true if the post has valid front matter and is published.
+fn check_front_matter(post: &VaultPost, timezone: Tz, problems: &mut Vec<Problem>) -> bool {
+    debug!("checking front matter");
+
+    if split_front_matter(&post.content).is_none() {
+        problems.push(problem(&post.path, 1, "missing front matter"));
+        return false;
+    }
+
+    match parse_front_matter(&post.path, &post.content, timezone) {
+        Ok(metadata) => metadata.published,
+        Err(BuildError::FrontMatter {
+            location, reason, ..
+        }) => {
+            let line = location.map_or(1, |location| location.line);
+            problems.push(problem(
+                &post.path,
+                line,
+                format!("invalid front matter: {reason}"),
+            ));
+            false
+        }
+        Err(_) => false,
+    }
+}
+
+fn check_markdown(
+    post: &VaultPost,
+    published: &HashSet<String>,
+    images: &Path,
+    languages: &Languages,
+    language_counts: &mut HashMap<String, usize>,
+    problems: &mut Vec<Problem>,
+) {
+    debug!("checking markdown");
+
+    let content = &post.content;
+    let diagnostics = Diagnostics::default();
+    let parser = Parser::new_ext(content, markdown_options()).into_offset_iter();
+    let mut events = resolve_wikilinks(parser, published, &diagnostics).peekable();
+
+    while let Some((event, range)) = events.next() {
+        let line = line_of(content, range.start);
+
+        match event {
+            Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(info))) => {
+                let Some(lang) = FenceInfo::parse(&info)
+                    .lang
+                    .filter(|lang| !languages.is_plain(lang))
+                else {
+                    continue;
+                };
+                let name = if let Some(canonical) = languages.canonical(&lang) {
+                    canonical.to_string()
+                } else {
+                    problems.push(problem(
+                        &post.path,
+                        line,
+                        format!("codeblock language `{lang}` has no tree-sitter parser"),
+                    ));
+                    lang.to_lowercase()
+                };
+                *language_counts.entry(name).or_default() += 1;
+            }
+            Event::Start(Tag::Image { dest_url, .. }) => {
+                let lower = dest_url.to_lowercase();
+                let is_absolute = lower.starts_with("http://")
+                    || lower.starts_with("https://")
+                    || lower.starts_with("data:")
+                    || dest_url.starts_with('/');
+
+                let file_name = percent_decode_str(&dest_url).decode_utf8_lossy();
+                if !is_absolute && !images.join(file_name.as_ref()).is_file() {
+                    problems.push(problem(
+                        &post.path,
+                        line,
+                        format!("broken image reference `{dest_url}`"),
+                    ));
+                }
+            }
+            Event::Start(Tag::BlockQuote(_)) => {
+                // Callout markers are split across several text events, eg. `[`, `!question`, `]`
+                let mut first_line = String::new();
+                while let Some((Event::Text(_) | Event::Start(Tag::Paragraph), _)) = events.peek() {
+                    if let Some((Event::Text(text), _)) = events.next() {
+                        first_line.push_str(&text);
+                    }
+                }
+
+                let Some(marker) = first_line.strip_prefix("[!") else {
+                    continue;
+                };
+                let marker = marker.split(']').next().unwrap_or_default().to_lowercase();
+
+                if !is_known_callout(&marker) {
+                    problems.push(problem(
+                        &post.path,
+                        line,
+                        format!("unknown callout type `{marker}`"),
+                    ));
+                }
+            }
+            _ => {}
+        }
+    }
+    drop(events);
+
+    let (_, warnings) = diagnostics.into_parts();
+    for warning in warnings {
+        problems.push(problem(
+            &post.path,
+            line_of(content, warning.offset()),
+            warning.to_string(),
+        ));
+    }
+}
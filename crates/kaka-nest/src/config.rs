@@ -0,0 +1,329 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use chrono_tz::Tz;
+use color_eyre::eyre::{Result, WrapErr, bail};
+use pullmark_parsers::{HighlightStyle, Languages, is_known_theme, theme_stylesheet};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info};
+
+/// The default location of the config file, relative to the working directory.
+pub const DEFAULT_CONFIG_PATH: &str = "./kaka-nest.toml";
+
+/// Everything read from `kaka-nest.toml`, any missing field falls back to its default.
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub site: SiteConfig,
+    pub paths: PathsConfig,
+    pub robots: RobotsConfig,
+    pub reading: ReadingConfig,
+    pub pagination: PaginationConfig,
+    pub tags: TagsConfig,
+    pub archive: ArchiveConfig,
+    pub related: RelatedConfig,
+    pub highlight: HighlightConfig,
+}
+
+/// The identity of the site, this is passed to every template as `site`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct SiteConfig {
+    pub title: String,
+    /// The url the site is hosted at, without a trailing slash.
+    pub base_url: String,
+    pub description: String,
+    pub author: String,
+    /// IANA timezone used for front matter dates without an offset, eg. `Pacific/Auckland`.
+    pub timezone: String,
+}
+
+impl Default for SiteConfig {
+    fn default() -> Self {
+        Self {
+            title: "My Blog".into(),
+            base_url: "http://127.0.0.1:8080".into(),
+            description: String::new(),
+            author: String::new(),
+            timezone: "UTC".into(),
+        }
+    }
+}
+
+impl SiteConfig {
+    /// The configured timezone, this is checked when the config is loaded.
+    #[must_use]
+    pub fn timezone(&self) -> Tz {
+        self.timezone.parse().unwrap_or(Tz::UTC)
+    }
+}
+
+/// The generated `robots.txt`.
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct RobotsConfig {
+    /// Paths crawlers shouldnt visit, eg. `/tags/`
+    pub disallow: Vec<String>,
+}
+
+/// How the reading time of a post is estimated.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReadingConfig {
+    /// Words read per minute, code blocks arent counted.
+    pub words_per_minute: u32,
+}
+
+impl Default for ReadingConfig {
+    fn default() -> Self {
+        Self {
+            words_per_minute: 200,
+        }
+    }
+}
+
+impl ReadingConfig {
+    /// Minutes it takes to read `word_count` words, rounded up to at least a minute.
+    #[must_use]
+    pub fn read_mins(&self, word_count: usize) -> u32 {
+        let minutes = word_count.div_ceil(self.words_per_minute as usize).max(1);
+        u32::try_from(minutes).unwrap_or(u32::MAX)
+    }
+}
+
+/// How many posts are listed on each page of the homepage and tag pages.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct PaginationConfig {
+    /// Posts on each page of the homepage, later pages are at `/page/2/` and so on
+    pub posts_per_page: usize,
+    /// Posts on each page of a tag, later pages are at `/tags/{tag}/page/2/` and so on
+    pub tag_posts_per_page: usize,
+}
+
+impl Default for PaginationConfig {
+    fn default() -> Self {
+        Self {
+            posts_per_page: 10,
+            tag_posts_per_page: 20,
+        }
+    }
+}
+
+/// The tag index at `/tags/`.
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct TagsConfig {
+    pub sort: TagSort,
+}
+
+/// The order tags are listed in on the tag index.
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TagSort {
+    /// Most used first, ties are sorted by name
+    #[default]
+    Count,
+    Name,
+}
+
+/// The archive at `/archive/`.
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct ArchiveConfig {
+    /// Also write a page for each year at `/archive/{year}/`
+    pub year_pages: bool,
+}
+
+/// The related posts listed at the end of each post.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct RelatedConfig {
+    /// How many related posts to list, 0 turns them off
+    pub count: usize,
+}
+
+impl Default for RelatedConfig {
+    fn default() -> Self {
+        Self { count: 3 }
+    }
+}
+
+/// How codeblocks are syntax highlighted, themes are names from the syntastica theme set
+/// like `one::dark`.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct HighlightConfig {
+    pub mode: HighlightMode,
+    /// The theme used by default, and in `inline` mode
+    pub theme: String,
+    /// The theme used when the reader prefers a light color scheme, only in `classes` mode
+    pub light_theme: Option<String>,
+    /// Extra names for codeblock languages, eg. `dockerfile = "bash"`, on top of built in
+    /// ones like `rs` and `yml`
+    pub aliases: BTreeMap<String, String>,
+}
+
+/// How highlighted tokens are colored.
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum HighlightMode {
+    /// Tokens get classes, colored by the generated `/highlight.css`
+    #[default]
+    Classes,
+    /// Tokens get inline styles
+    Inline,
+}
+
+impl Default for HighlightConfig {
+    fn default() -> Self {
+        Self {
+            mode: HighlightMode::default(),
+            theme: "one::dark".into(),
+            light_theme: Some("one::light".into()),
+            aliases: BTreeMap::new(),
+        }
+    }
+}
+
+impl HighlightConfig {
+    /// The style passed to the highlighting pass, the themes are checked when the config is loaded.
+    #[must_use]
+    pub fn style(&self) -> HighlightStyle {
+        match self.mode {
+            HighlightMode::Classes => HighlightStyle::Classes,
+            HighlightMode::Inline => {
+                HighlightStyle::inline(&self.theme).unwrap_or(HighlightStyle::Classes)
+            }
+        }
+    }
+
+    /// Maps codeblock languages to parsers, with the aliases from the config.
+    #[must_use]
+    pub fn languages(&self) -> Languages {
+        Languages::new(&self.aliases)
+    }
+
+    /// The css for `/highlight.css`, in inline mode only the codeblock colors are used.
+    #[must_use]
+    pub fn stylesheet(&self) -> String {
+        let light = match self.mode {
+            HighlightMode::Classes => self.light_theme.as_deref(),
+            HighlightMode::Inline => None,
+        };
+        theme_stylesheet(&self.theme, light).unwrap_or_default()
+    }
+}
+
+/// Where the tool reads its inputs from and writes its output to.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct PathsConfig {
+    /// The obsidian vault containing the posts, images are read from `{content}/images`.
+    pub content: PathBuf,
+    pub templates: PathBuf,
+    /// Files copied as-is into the root of the output directory.
+    #[serde(rename = "static")]
+    pub static_files: PathBuf,
+    pub output: PathBuf,
+    /// The manifest used for incremental builds.
+    pub cache: PathBuf,
+}
+
+impl Default for PathsConfig {
+    fn default() -> Self {
+        Self {
+            content: "./assets/blog".into(),
+            templates: "./assets/templates".into(),
+            static_files: "./assets/static".into(),
+            output: "./output".into(),
+            cache: "./.kaka/build-cache.json".into(),
+        }
+    }
+}
+
+impl PathsConfig {
+    #[must_use]
+    pub fn images(&self) -> PathBuf {
+        self.content.join("images")
+    }
+}
+
+impl Config {
+    /// Loads [`DEFAULT_CONFIG_PATH`], using the defaults if it doesnt exist.
+    ///
+    /// # Errors
+    /// - Reading the config file
+    /// - Parsing the config file
+    pub fn load_default() -> Result<Self> {
+        let path = Path::new(DEFAULT_CONFIG_PATH);
+        if !path.exists() {
+            info!("no config file at {}, using defaults", path.display());
+            return Ok(Self::default());
+        }
+
+        Self::load(path)
+    }
+
+    /// Loads the config file at `path`, which has to exist.
+    ///
+    /// # Errors
+    /// - Reading the config file
+    /// - Parsing the config file
+    pub fn load(path: &Path) -> Result<Self> {
+        debug!("reading config from {}", path.display());
+        let contents = fs::read_to_string(path)
+            .wrap_err_with(|| format!("Failed to read config file {}", path.display()))?;
+        let mut config: Self = toml::from_str(&contents)
+            .wrap_err_with(|| format!("Failed to parse config file {}", path.display()))?;
+
+        if let Err(err) = config.site.timezone.parse::<Tz>() {
+            bail!("Invalid timezone in config file {}: {err}", path.display());
+        }
+        if config.pagination.posts_per_page == 0 || config.pagination.tag_posts_per_page == 0 {
+            bail!(
+                "Invalid pagination in config file {}: posts per page must be greater than 0",
+                path.display()
+            );
+        }
+        let themes = [
+            Some(&config.highlight.theme),
+            config.highlight.light_theme.as_ref(),
+        ];
+        if let Some(theme) = themes
+            .into_iter()
+            .flatten()
+            .find(|theme| !is_known_theme(theme))
+        {
+            bail!(
+                "Invalid highlight theme `{theme}` in config file {}",
+                path.display()
+            );
+        }
+        if let Some((alias, lang)) = config
+            .highlight
+            .aliases
+            .iter()
+            .find(|(_, lang)| !Languages::default().is_known(lang))
+        {
+            bail!(
+                "Invalid highlight alias `{alias}` in config file {}: `{lang}` isnt a known language",
+                path.display()
+            );
+        }
+        if config.reading.words_per_minute == 0 {
+            bail!(
+                "Invalid words_per_minute in config file {}: must be greater than 0",
+                path.display()
+            );
+        }
+
+        let base_url = config.site.base_url.trim_end_matches('/').len();
+        config.site.base_url.truncate(base_url);
+
+        Ok(config)
+    }
+}
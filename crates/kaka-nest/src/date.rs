@@ -0,0 +1,156 @@
+use std::fmt;
+
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use serde::{Serialize, Serializer};
+
+/// Formats accepted for dates without a UTC offset, these are in the site's timezone.
+const LOCAL_FORMATS: &[&str] = &[
+    "%Y-%m-%d %H:%M:%S",
+    "%Y-%m-%dT%H:%M:%S",
+    "%Y-%m-%d %H:%M",
+    "%Y-%m-%dT%H:%M",
+];
+
+/// A date from the front matter of a post.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PostDate(DateTime<FixedOffset>);
+
+impl PostDate {
+    /// Parses `2025-08-23`, `2025-08-23 18:30` or an RFC 3339 timestamp, dates without an
+    /// offset are in `timezone`.
+    ///
+    /// # Errors
+    /// - `date` isnt in one of the accepted formats
+    /// - `date` doesnt exist in `timezone`, eg. it is skipped by daylight saving
+    pub fn parse(date: &str, timezone: Tz) -> Result<Self, String> {
+        let date = date.trim();
+        if let Ok(datetime) = DateTime::parse_from_rfc3339(date) {
+            return Ok(Self(datetime));
+        }
+
+        let local = LOCAL_FORMATS
+            .iter()
+            .find_map(|format| NaiveDateTime::parse_from_str(date, format).ok())
+            .or_else(|| {
+                NaiveDate::parse_from_str(date, "%Y-%m-%d")
+                    .ok()
+                    .map(|date| date.and_time(NaiveTime::MIN))
+            })
+            .ok_or_else(|| {
+                format!(
+                    "`{date}` isnt a valid date, expected YYYY-MM-DD, YYYY-MM-DD HH:MM or RFC 3339"
+                )
+            })?;
+
+        timezone
+            .from_local_datetime(&local)
+            .earliest()
+            .map(|datetime| Self(datetime.fixed_offset()))
+            .ok_or_else(|| format!("`{date}` doesnt exist in the {timezone} timezone"))
+    }
+
+    /// The current time in `timezone`.
+    #[must_use]
+    pub fn now(timezone: Tz) -> Self {
+        Self(Utc::now().with_timezone(&timezone).fixed_offset())
+    }
+
+    #[must_use]
+    pub fn datetime(&self) -> DateTime<FixedOffset> {
+        self.0
+    }
+}
+
+impl fmt::Display for PostDate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0.format("%Y-%m-%d"))
+    }
+}
+
+/// Templates and new posts get the date without its time.
+impl Serialize for PostDate {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono_tz::Pacific::Auckland;
+
+    use super::*;
+
+    fn rfc3339(date: &str) -> String {
+        PostDate::parse(date, Auckland)
+            .unwrap()
+            .datetime()
+            .to_rfc3339()
+    }
+
+    #[test]
+    fn date_only_is_midnight_in_timezone() {
+        assert_eq!(rfc3339("2025-08-23"), "2025-08-23T00:00:00+12:00");
+        // Daylight saving time
+        assert_eq!(rfc3339("2025-01-10"), "2025-01-10T00:00:00+13:00");
+    }
+
+    #[test]
+    fn local_formats() {
+        for date in [
+            "2025-08-23 18:30",
+            "2025-08-23T18:30",
+            "2025-08-23 18:30:00",
+            "2025-08-23T18:30:00",
+        ] {
+            assert_eq!(rfc3339(date), "2025-08-23T18:30:00+12:00", "{date}");
+        }
+    }
+
+    #[test]
+    fn rfc3339_keeps_its_offset() {
+        assert_eq!(rfc3339("2025-08-23T18:30:00Z"), "2025-08-23T18:30:00+00:00");
+        assert_eq!(
+            rfc3339("2025-08-23T18:30:00-05:00"),
+            "2025-08-23T18:30:00-05:00"
+        );
+    }
+
+    #[test]
+    fn surrounding_whitespace_is_ignored() {
+        assert_eq!(rfc3339("  2025-08-23 \n"), "2025-08-23T00:00:00+12:00");
+    }
+
+    #[test]
+    fn invalid_dates() {
+        for date in ["", "yesterday", "23/08/2025", "2025-13-01", "2025-02-30"] {
+            let err = PostDate::parse(date, Auckland).unwrap_err();
+            assert!(err.contains("isnt a valid date"), "{date}: {err}");
+        }
+    }
+
+    #[test]
+    fn skipped_by_daylight_saving() {
+        let err = PostDate::parse("2024-09-29 02:30", Auckland).unwrap_err();
+        assert!(err.contains("doesnt exist in the Pacific/Auckland timezone"));
+    }
+
+    #[test]
+    fn repeated_by_daylight_saving_uses_earliest() {
+        assert_eq!(rfc3339("2024-04-07 02:30"), "2024-04-07T02:30:00+13:00");
+    }
+
+    #[test]
+    fn display_and_serialize_as_date() {
+        let date = PostDate::parse("2025-08-23T23:59:00+12:00", Auckland).unwrap();
+        assert_eq!(date.to_string(), "2025-08-23");
+        assert_eq!(serde_json::to_string(&date).unwrap(), r#""2025-08-23""#);
+    }
+
+    #[test]
+    fn ordered_by_instant() {
+        let earlier = PostDate::parse("2025-08-23 10:00", Auckland).unwrap();
+        let later = PostDate::parse("2025-08-23T00:00:00Z", Auckland).unwrap();
+        assert!(earlier < later);
+    }
+}
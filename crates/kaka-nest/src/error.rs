@@ -0,0 +1,208 @@
+use std::{
+    fmt::{self, Write},
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use color_eyre::{Section, SectionExt, eyre::Report};
+use serde::{Deserialize, Serialize};
+use shared_utils::TemplateError;
+use thiserror::Error;
+
+use crate::util::line_of;
+
+/// A 1-based line and column in a source file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Location {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Location {
+    /// Converts a byte offset into `content` to a line and column.
+    #[must_use]
+    pub fn from_offset(content: &str, offset: usize) -> Self {
+        let line_start = content[..offset].rfind('\n').map_or(0, |index| index + 1);
+
+        Self {
+            line: line_of(content, offset),
+            column: content[line_start..offset].chars().count() + 1,
+        }
+    }
+}
+
+/// The part of the build which failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    FrontMatter,
+    Markdown,
+    Template,
+    Io,
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::FrontMatter => "front matter",
+            Self::Markdown => "markdown",
+            Self::Template => "template",
+            Self::Io => "io",
+        })
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum BuildError {
+    #[error("invalid front matter in {}: {reason}", path.display())]
+    FrontMatter {
+        path: PathBuf,
+        location: Option<Location>,
+        reason: String,
+    },
+    #[error("failed to render the markdown in {}", path.display())]
+    Markdown {
+        path: PathBuf,
+        location: Location,
+        #[source]
+        source: pullmark_parsers::Error,
+    },
+    #[error("failed to render {}", path.display())]
+    Template {
+        path: PathBuf,
+        #[source]
+        source: TemplateError,
+    },
+    #[error("failed to rewrite the html of {}", path.display())]
+    Rewrite {
+        path: PathBuf,
+        #[source]
+        source: lol_html::errors::RewritingError,
+    },
+    #[error("{} has an invalid file name", path.display())]
+    InvalidFileName { path: PathBuf },
+    #[error("failed to access {}", path.display())]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error("failed to copy {}", path.display())]
+    Copy {
+        path: PathBuf,
+        #[source]
+        source: fs_extra::error::Error,
+    },
+    #[error("failed to create the worker thread pool")]
+    ThreadPool(#[from] rayon::ThreadPoolBuildError),
+}
+
+impl BuildError {
+    #[must_use]
+    pub fn stage(&self) -> Stage {
+        match self {
+            Self::FrontMatter { .. } => Stage::FrontMatter,
+            Self::Markdown { source, .. } => match source {
+                pullmark_parsers::Error::Template { .. } => Stage::Template,
+            },
+            Self::Template { .. } | Self::Rewrite { .. } => Stage::Template,
+            Self::InvalidFileName { .. }
+            | Self::Io { .. }
+            | Self::Copy { .. }
+            | Self::ThreadPool(_) => Stage::Io,
+        }
+    }
+
+    /// The file and position the error points at, for templates this is the template file.
+    #[must_use]
+    pub fn location(&self) -> Option<(PathBuf, Location)> {
+        match self {
+            Self::FrontMatter {
+                path,
+                location: Some(location),
+                ..
+            }
+            | Self::Markdown { path, location, .. } => Some((path.clone(), *location)),
+            Self::Template { source, .. } => source
+                .location()
+                .map(|(path, line, column)| (path, Location { line, column })),
+            _ => None,
+        }
+    }
+
+    /// Converts the error into a report showing the failing stage and a snippet of the source.
+    pub fn into_report(self) -> Report {
+        let stage = self.stage();
+        let location = self.location();
+
+        let mut report = Report::new(self).with_section(|| stage.to_string().header("Stage:"));
+        if let Some((path, location)) = location {
+            let position = format!("{}:{}:{}", path.display(), location.line, location.column);
+            report = report.with_section(|| position.header("File:"));
+
+            if let Some(snippet) = source_snippet(&path, location) {
+                report = report.with_section(|| snippet.header("Source:"));
+            }
+        }
+
+        report
+    }
+}
+
+/// A problem which didnt stop a post from building, in strict mode these fail the build.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BuildWarning {
+    pub path: PathBuf,
+    pub location: Location,
+    pub message: String,
+}
+
+impl fmt::Display for BuildWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}: {}",
+            self.path.display(),
+            self.location.line,
+            self.location.column,
+            self.message
+        )
+    }
+}
+
+/// Formats the lines around `location`, with a marker under the failing column.
+fn source_snippet(path: &Path, location: Location) -> Option<String> {
+    const CONTEXT: usize = 2;
+
+    let content = fs::read_to_string(path).ok()?;
+    let first = location.line.saturating_sub(CONTEXT).max(1);
+
+    let mut snippet = String::new();
+    for (number, line) in content
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line))
+        .skip(first - 1)
+        .take(location.line - first + CONTEXT + 1)
+    {
+        let _ = writeln!(snippet, "{number:>4} | {line}");
+        if number == location.line {
+            let _ = writeln!(snippet, "     | {}^", " ".repeat(location.column - 1));
+        }
+    }
+
+    Some(snippet)
+}
+
+/// Attaches the path being accessed to io errors.
+pub(crate) trait IoResultExt<T> {
+    fn with_path(self, path: &Path) -> Result<T, BuildError>;
+}
+
+impl<T> IoResultExt<T> for io::Result<T> {
+    fn with_path(self, path: &Path) -> Result<T, BuildError> {
+        self.map_err(|source| BuildError::Io {
+            path: path.to_path_buf(),
+            source,
+        })
+    }
+}
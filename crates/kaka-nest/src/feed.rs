@@ -0,0 +1,235 @@
+use std::fs;
+
+use atom_syndication::{
+    CategoryBuilder, ContentBuilder, EntryBuilder, FeedBuilder, FixedDateTime, LinkBuilder,
+    PersonBuilder, Text,
+};
+use chrono::DateTime;
+use rss::{Category, Channel, ChannelBuilder, ItemBuilder};
+use serde::Serialize;
+use tracing::{Level, debug, info, span};
+
+use crate::{
+    build_page::{Post, PostList},
+    config::Config,
+    error::{BuildError, IoResultExt},
+};
+
+pub(crate) fn output_rss_to_fs(blogs: &PostList, config: &Config) -> Result<(), BuildError> {
+    let span = span!(Level::INFO, "output rss");
+    let _enter = span.enter();
+    let site = &config.site;
+
+    let channel = rss_channel(
+        site.title.clone(),
+        site.base_url.clone(),
+        site.description.clone(),
+        &blogs.blogs,
+        config,
+    );
+
+    debug!("writing rss to fs");
+    let rss_file = config.paths.output.join("index.xml");
+    fs::write(&rss_file, channel.to_string()).with_path(&rss_file)?;
+
+    Ok(())
+    // todo!()
+}
+
+/// Builds an RSS 2.0 channel holding `posts`.
+pub(crate) fn rss_channel<'a>(
+    title: String,
+    link: String,
+    description: String,
+    posts: impl IntoIterator<Item = &'a Post>,
+    config: &Config,
+) -> Channel {
+    let site = &config.site;
+
+    info!("creating channel");
+    let mut channel = ChannelBuilder::default()
+        .title(title)
+        .link(link)
+        .description(description)
+        .build();
+
+    // TODO fix all the .clones here
+    for post in posts {
+        let span = span!(Level::INFO, "post catagories", post = post.title);
+        let _enter = span.enter();
+
+        let catagories: Vec<Category> = match post.metadata.tags.as_ref() {
+            Some(tags) => tags
+                .iter()
+                .map(|name| Category {
+                    name: name.clone(),
+                    domain: None,
+                })
+                .collect(),
+            None => vec![],
+        };
+
+        let rss_post = ItemBuilder::default()
+            .title(post.title.clone())
+            .author(site.author.clone())
+            .categories(catagories)
+            .pub_date(post.metadata.date.datetime().to_rfc2822())
+            .content(post.contents.clone())
+            .link(format!("{}/posts/{}/index.html", site.base_url, post.slug))
+            .build();
+
+        info!("finished post");
+
+        channel.items.push(rss_post);
+    }
+
+    channel
+}
+
+/// Writes an Atom 1.0 feed to `atom.xml`.
+pub(crate) fn output_atom_to_fs(blogs: &PostList, config: &Config) -> Result<(), BuildError> {
+    let span = span!(Level::INFO, "output atom");
+    let _enter = span.enter();
+    let site = &config.site;
+
+    info!("creating atom feed");
+    let author = PersonBuilder::default().name(site.author.clone()).build();
+    let entries: Vec<_> = blogs
+        .blogs
+        .iter()
+        .map(|post| {
+            let url = post_url(config, post);
+            let categories: Vec<_> = post
+                .metadata
+                .tags
+                .iter()
+                .flatten()
+                .map(|tag| CategoryBuilder::default().term(tag.clone()).build())
+                .collect();
+
+            EntryBuilder::default()
+                .title(Text::plain(post.title.clone()))
+                .id(url.clone())
+                .updated(post.metadata.lastmod().datetime())
+                .published(Some(post.metadata.date.datetime()))
+                .author(author.clone())
+                .categories(categories)
+                .link(LinkBuilder::default().href(url).rel("alternate").build())
+                .summary(post.metadata.description.clone().map(Text::plain))
+                .content(Some(
+                    ContentBuilder::default()
+                        .value(Some(post.contents.clone()))
+                        .content_type(Some("html".to_string()))
+                        .build(),
+                ))
+                .build()
+        })
+        .collect();
+
+    let feed = FeedBuilder::default()
+        .title(Text::plain(site.title.clone()))
+        .id(format!("{}/", site.base_url))
+        .updated(newest_date(blogs))
+        .author(author)
+        .subtitle(Some(Text::plain(site.description.clone())))
+        .link(
+            LinkBuilder::default()
+                .href(format!("{}/", site.base_url))
+                .rel("alternate")
+                .build(),
+        )
+        .link(
+            LinkBuilder::default()
+                .href(format!("{}/atom.xml", site.base_url))
+                .rel("self")
+                .build(),
+        )
+        .entries(entries)
+        .build();
+
+    debug!("writing atom to fs");
+    let atom_file = config.paths.output.join("atom.xml");
+    fs::write(&atom_file, feed.to_string()).with_path(&atom_file)
+}
+
+/// A JSON Feed 1.1, see <https://www.jsonfeed.org/version/1.1/>.
+#[derive(Serialize)]
+struct JsonFeed<'a> {
+    version: &'static str,
+    title: &'a str,
+    home_page_url: String,
+    feed_url: String,
+    description: &'a str,
+    authors: [JsonFeedAuthor<'a>; 1],
+    items: Vec<JsonFeedItem<'a>>,
+}
+
+#[derive(Serialize)]
+struct JsonFeedAuthor<'a> {
+    name: &'a str,
+}
+
+#[derive(Serialize)]
+struct JsonFeedItem<'a> {
+    id: String,
+    url: String,
+    title: &'a str,
+    content_html: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    summary: Option<&'a str>,
+    date_published: String,
+    date_modified: String,
+    tags: &'a [String],
+}
+
+/// Writes a JSON Feed 1.1 to `feed.json`.
+pub(crate) fn output_json_feed_to_fs(blogs: &PostList, config: &Config) -> Result<(), BuildError> {
+    let span = span!(Level::INFO, "output json feed");
+    let _enter = span.enter();
+    let site = &config.site;
+
+    info!("creating json feed");
+    let items = blogs
+        .blogs
+        .iter()
+        .map(|post| JsonFeedItem {
+            id: post_url(config, post),
+            url: post_url(config, post),
+            title: &post.title,
+            content_html: &post.contents,
+            summary: post.metadata.description.as_deref(),
+            date_published: post.metadata.date.datetime().to_rfc3339(),
+            date_modified: post.metadata.lastmod().datetime().to_rfc3339(),
+            tags: post.metadata.tags.as_deref().unwrap_or_default(),
+        })
+        .collect();
+
+    let feed = JsonFeed {
+        version: "https://jsonfeed.org/version/1.1",
+        title: &site.title,
+        home_page_url: format!("{}/", site.base_url),
+        feed_url: format!("{}/feed.json", site.base_url),
+        description: &site.description,
+        authors: [JsonFeedAuthor { name: &site.author }],
+        items,
+    };
+
+    debug!("writing json feed to fs");
+    let json_file = config.paths.output.join("feed.json");
+    let json = serde_json::to_string_pretty(&feed).map_err(std::io::Error::other);
+    fs::write(&json_file, json.with_path(&json_file)?).with_path(&json_file)
+}
+
+fn post_url(config: &Config, post: &Post) -> String {
+    format!("{}/posts/{}", config.site.base_url, post.slug)
+}
+
+/// When the most recent post changed, used as the updated time of the whole feed.
+fn newest_date(blogs: &PostList) -> FixedDateTime {
+    blogs
+        .blogs
+        .iter()
+        .map(|post| post.metadata.lastmod().datetime())
+        .max()
+        .unwrap_or(DateTime::UNIX_EPOCH.fixed_offset())
+}
@@ -1,3 +1,16 @@
 #![warn(clippy::pedantic)]
+mod archive;
 pub mod build_page;
+mod cache;
+pub mod check;
+pub mod config;
+pub mod date;
+pub mod error;
+mod feed;
+pub mod new_post;
+mod pagination;
+mod related;
+mod sitemap;
+pub mod tags;
 pub mod util;
+pub mod watch;
@@ -1,23 +1,75 @@
 #![warn(clippy::pedantic)]
 
-use clap::{Parser, Subcommand};
-use kaka_nest::build_page::create_blog_on_system;
-use server_view::start_file_server;
-use tracing::info;
+use std::path::PathBuf;
+
+use clap::{Args, Parser, Subcommand};
+use color_eyre::eyre::bail;
+use kaka_nest::{
+    build_page::{BuildOptions, create_blog_on_system},
+    check::check_vault,
+    config::Config,
+    error::{BuildError, BuildWarning},
+    new_post::create_new_post,
+    watch::watch_and_rebuild,
+};
+use server_view::{LiveReload, start_file_server};
 
 #[derive(Parser, Debug)]
 struct Cli {
     #[arg(short, long)]
     verbose: bool,
 
+    /// Path to the config file, defaults to `./kaka-nest.toml` if it exists
+    #[arg(short, long)]
+    config: Option<PathBuf>,
+
     #[command(subcommand)]
     command: Option<Commands>,
 }
 
 #[derive(Subcommand, Clone, Debug)]
 enum Commands {
-    Build,
-    Serve,
+    Build(BuildArgs),
+    /// Build the site, then serve it and rebuild whenever a file changes
+    Serve(BuildArgs),
+    /// Check every post for problems without writing any output
+    Check,
+    /// Create a new unpublished post in the vault
+    New {
+        /// The title of the post, also used as its file name
+        title: String,
+
+        /// Comma separated list of tags
+        #[arg(short, long, value_delimiter = ',')]
+        tags: Vec<String>,
+
+        #[arg(short, long)]
+        description: Option<String>,
+    },
+}
+
+#[derive(Args, Clone, Debug, Default)]
+struct BuildArgs {
+    /// Ignore the build cache and rebuild every post
+    #[arg(short, long)]
+    force: bool,
+
+    /// Number of threads used to render posts, defaults to the number of CPU cores
+    #[arg(short, long)]
+    jobs: Option<usize>,
+
+    /// Exit with an error if the build produced any warnings
+    #[arg(long)]
+    strict: bool,
+}
+
+impl From<BuildArgs> for BuildOptions {
+    fn from(args: BuildArgs) -> Self {
+        Self {
+            force: args.force,
+            jobs: args.jobs,
+        }
+    }
 }
 
 #[actix_web::main]
@@ -26,13 +78,78 @@ async fn main() -> color_eyre::eyre::Result<()> {
     tracing_subscriber::fmt::init();
 
     let args = Cli::parse();
+    let config = match &args.config {
+        Some(path) => Config::load(path)?,
+        None => Config::load_default()?,
+    };
+    shared_utils::load_templates(
+        &config.paths.templates,
+        matches!(args.command, Some(Commands::Serve(_))),
+    )
+    .map_err(|source| {
+        BuildError::Template {
+            path: config.paths.templates.clone(),
+            source,
+        }
+        .into_report()
+    })?;
 
-    match args.command.unwrap_or(Commands::Build) {
-        Commands::Build => {
-            create_blog_on_system().unwrap();
+    match args
+        .command
+        .unwrap_or_else(|| Commands::Build(BuildArgs::default()))
+    {
+        Commands::Build(build_args) => {
+            let strict = build_args.strict;
+            let warnings = create_blog_on_system(&config, &build_args.into())
+                .map_err(BuildError::into_report)?;
+            fail_on_warnings(strict, &warnings)?;
+        }
+        Commands::Serve(build_args) => {
+            let strict = build_args.strict;
+            let options = build_args.into();
+            let warnings =
+                create_blog_on_system(&config, &options).map_err(BuildError::into_report)?;
+            fail_on_warnings(strict, &warnings)?;
+
+            let live_reload = LiveReload::new();
+            let output = config.paths.output.clone();
+            let _watcher = watch_and_rebuild(config, options, live_reload.clone())?;
+
+            start_file_server(output, Some(live_reload)).await?;
+        }
+        Commands::Check => {
+            let report = check_vault(&config)?;
+            let problems = report.problems;
+            for problem in &problems {
+                println!("{problem}");
+            }
+
+            if !report.languages.is_empty() {
+                let languages: Vec<_> = report.languages.iter().map(ToString::to_string).collect();
+                println!("languages used: {}", languages.join(", "));
+            }
+
+            if !problems.is_empty() {
+                println!("found {} problems", problems.len());
+                std::process::exit(1);
+            }
+            println!("no problems found");
+        }
+        Commands::New {
+            title,
+            tags,
+            description,
+        } => {
+            create_new_post(&config, &title, &tags, description)?;
         }
-        Commands::Serve => start_file_server().await?,
     }
 
     Ok(())
 }
+
+fn fail_on_warnings(strict: bool, warnings: &[BuildWarning]) -> color_eyre::eyre::Result<()> {
+    if strict && !warnings.is_empty() {
+        bail!("build produced {} warnings in strict mode", warnings.len());
+    }
+    Ok(())
+}
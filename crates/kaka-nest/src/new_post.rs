@@ -0,0 +1,66 @@
+use std::{fs, path::PathBuf};
+
+use color_eyre::eyre::{Result, bail, eyre};
+use slugify::slugify;
+use tracing::info;
+
+use crate::{
+    build_page::{PostMetadata, generate_title_from_path},
+    config::Config,
+    date::PostDate,
+    util::get_blog_paths,
+};
+
+/// Creates an unpublished post in the vault, with its front matter filled in.
+///
+/// Returns the path of the new markdown file.
+///
+/// # Errors
+/// - The title cant be used as a file name
+/// - Another post already has the same slug
+/// - Writing the new file
+pub fn create_new_post(
+    config: &Config,
+    title: &str,
+    tags: &[String],
+    description: Option<String>,
+) -> Result<PathBuf> {
+    if title.trim().is_empty() || title.contains(['/', '\\']) {
+        bail!("`{title}` cant be used as a post title");
+    }
+
+    let slug = slugify!(title);
+    for path in get_blog_paths(&config.paths.content)? {
+        let existing = generate_title_from_path(&path)
+            .ok_or_else(|| eyre!("invalid file name {}", path.display()))?;
+
+        if slugify!(existing) == slug {
+            bail!(
+                "a post with the slug `{slug}` already exists at {}",
+                path.display()
+            );
+        }
+    }
+
+    let metadata = PostMetadata {
+        date: PostDate::now(config.site.timezone()),
+        updated: None,
+        published: false,
+        // Obsidian writes tags with a leading #, keep new posts consistent with it
+        tags: Some(
+            tags.iter()
+                .map(|tag| format!("#{}", tag.trim_start_matches('#')))
+                .collect(),
+        ),
+        read_mins: None,
+        description,
+        toc: false,
+    };
+
+    let path = config.paths.content.join(format!("{title}.md"));
+    let front_matter = serde_yaml::to_string(&metadata)?;
+    fs::write(&path, format!("---\n{front_matter}---\n\n"))?;
+
+    info!("created {}", path.display());
+    Ok(path)
+}
@@ -0,0 +1,164 @@
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+/// A link to another page of a paginated list.
+#[derive(Serialize, Debug)]
+pub(crate) struct PageLink {
+    pub number: usize,
+    pub url: String,
+    pub current: bool,
+}
+
+/// The `pagination` context passed to the homepage and tag pages.
+#[derive(Serialize, Debug)]
+pub(crate) struct Pagination {
+    /// The 1-based number of this page
+    pub current: usize,
+    pub total: usize,
+    pub prev: Option<String>,
+    pub next: Option<String>,
+    pub pages: Vec<PageLink>,
+}
+
+/// One page of a paginated list.
+pub(crate) struct Page<'a, T> {
+    pub items: &'a [T],
+    pub pagination: Pagination,
+    /// Where the page is written, relative to the output directory of the list
+    pub file: PathBuf,
+}
+
+/// Splits `items` into pages of `per_page`, the first page is at `base_url` and the
+/// rest at `{base_url}page/{n}/`.
+///
+/// There is always at least one page, even when `items` is empty.
+pub(crate) fn paginate<'a, T>(items: &'a [T], per_page: usize, base_url: &str) -> Vec<Page<'a, T>> {
+    let chunks: Vec<_> = if items.is_empty() {
+        vec![items]
+    } else {
+        items.chunks(per_page.max(1)).collect()
+    };
+    let total = chunks.len();
+
+    chunks
+        .into_iter()
+        .enumerate()
+        .map(|(index, items)| {
+            let current = index + 1;
+            let pages = (1..=total)
+                .map(|number| PageLink {
+                    number,
+                    url: page_url(base_url, number),
+                    current: number == current,
+                })
+                .collect();
+
+            Page {
+                items,
+                pagination: Pagination {
+                    current,
+                    total,
+                    prev: (current > 1).then(|| page_url(base_url, current - 1)),
+                    next: (current < total).then(|| page_url(base_url, current + 1)),
+                    pages,
+                },
+                file: page_file(current),
+            }
+        })
+        .collect()
+}
+
+fn page_url(base_url: &str, number: usize) -> String {
+    if number == 1 {
+        base_url.to_string()
+    } else {
+        format!("{base_url}page/{number}/")
+    }
+}
+
+fn page_file(number: usize) -> PathBuf {
+    if number == 1 {
+        PathBuf::from("index.html")
+    } else {
+        Path::new("page")
+            .join(number.to_string())
+            .join("index.html")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_list_has_one_page() {
+        let pages = paginate::<u32>(&[], 10, "/");
+        assert_eq!(pages.len(), 1);
+        assert!(pages[0].items.is_empty());
+        assert_eq!(pages[0].pagination.total, 1);
+        assert_eq!(pages[0].pagination.prev, None);
+        assert_eq!(pages[0].pagination.next, None);
+        assert_eq!(pages[0].file, PathBuf::from("index.html"));
+    }
+
+    #[test]
+    fn exact_fit_has_one_page() {
+        let pages = paginate(&[1, 2, 3], 3, "/");
+        assert_eq!(pages.len(), 1);
+        assert_eq!(pages[0].items, [1, 2, 3]);
+    }
+
+    #[test]
+    fn last_page_holds_the_remainder() {
+        let pages = paginate(&[1, 2, 3, 4, 5], 2, "/");
+        let items: Vec<_> = pages.iter().map(|page| page.items).collect();
+        assert_eq!(items, [&[1, 2][..], &[3, 4], &[5]]);
+    }
+
+    #[test]
+    fn page_urls_and_files() {
+        let pages = paginate(&[1, 2, 3], 1, "/tags/linux/");
+
+        let first = &pages[0].pagination;
+        assert_eq!(first.current, 1);
+        assert_eq!(first.prev, None);
+        assert_eq!(first.next.as_deref(), Some("/tags/linux/page/2/"));
+        assert_eq!(pages[0].file, PathBuf::from("index.html"));
+
+        let second = &pages[1].pagination;
+        assert_eq!(second.prev.as_deref(), Some("/tags/linux/"));
+        assert_eq!(second.next.as_deref(), Some("/tags/linux/page/3/"));
+        assert_eq!(pages[1].file, Path::new("page/2/index.html"));
+
+        let last = &pages[2].pagination;
+        assert_eq!(last.prev.as_deref(), Some("/tags/linux/page/2/"));
+        assert_eq!(last.next, None);
+        assert_eq!(pages[2].file, Path::new("page/3/index.html"));
+    }
+
+    #[test]
+    fn page_links_mark_the_current_page() {
+        let pages = paginate(&[1, 2, 3], 1, "/");
+        for page in &pages {
+            let links = &page.pagination.pages;
+            assert_eq!(links.len(), 3);
+            let current: Vec<_> = links.iter().filter(|link| link.current).collect();
+            assert_eq!(current.len(), 1);
+            assert_eq!(current[0].number, page.pagination.current);
+        }
+        let urls: Vec<_> = pages[0]
+            .pagination
+            .pages
+            .iter()
+            .map(|link| link.url.as_str())
+            .collect();
+        assert_eq!(urls, ["/", "/page/2/", "/page/3/"]);
+    }
+
+    #[test]
+    fn zero_per_page_is_treated_as_one() {
+        let pages = paginate(&[1, 2], 0, "/");
+        assert_eq!(pages.len(), 2);
+    }
+}
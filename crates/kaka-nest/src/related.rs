@@ -0,0 +1,139 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::Serialize;
+
+use crate::{build_page::Post, date::PostDate};
+
+/// A link to another post from a post page.
+#[derive(Serialize, Debug, Clone, Copy)]
+pub(crate) struct PostLink<'a> {
+    pub title: &'a str,
+    pub slug: &'a str,
+    pub date: PostDate,
+    pub description: Option<&'a str>,
+}
+
+impl<'a> From<&'a Post> for PostLink<'a> {
+    fn from(post: &'a Post) -> Self {
+        Self {
+            title: &post.title,
+            slug: &post.slug,
+            date: post.metadata.date,
+            description: post.metadata.description.as_deref(),
+        }
+    }
+}
+
+/// Where a reader can go after finishing a post, passed to the `blog` template.
+#[derive(Serialize, Debug, Default)]
+pub(crate) struct PostNavigation<'a> {
+    /// The post written before this one
+    pub previous: Option<PostLink<'a>>,
+    /// The post written after this one
+    pub next: Option<PostLink<'a>>,
+    /// The most related posts first
+    pub related: Vec<PostLink<'a>>,
+}
+
+/// Finds the navigation for every post in `posts`, which are sorted newest first.
+///
+/// Related posts are ranked by how many tags they share, ties are broken by how similar
+/// their text is. At most `max_related` are returned for each post.
+#[allow(clippy::cast_precision_loss)]
+pub(crate) fn post_navigation(posts: &[Post], max_related: usize) -> Vec<PostNavigation<'_>> {
+    let vectors = term_vectors(posts);
+    let tags: Vec<HashSet<&str>> = posts
+        .iter()
+        .map(|post| post.tags.iter().map(|tag| tag.url.as_str()).collect())
+        .collect();
+
+    (0..posts.len())
+        .map(|index| {
+            let mut scored: Vec<_> = (0..posts.len())
+                .filter(|&other| other != index)
+                .filter_map(|other| {
+                    let shared = tags[index].intersection(&tags[other]).count();
+                    let similarity = cosine_similarity(&vectors[index], &vectors[other]);
+                    let score = shared as f64 + similarity;
+                    (score > 0.0).then_some((score, other))
+                })
+                .collect();
+            scored.sort_by(|a, b| b.0.total_cmp(&a.0).then(a.1.cmp(&b.1)));
+
+            PostNavigation {
+                previous: posts.get(index + 1).map(PostLink::from),
+                next: index
+                    .checked_sub(1)
+                    .and_then(|newer| posts.get(newer))
+                    .map(PostLink::from),
+                related: scored
+                    .into_iter()
+                    .take(max_related)
+                    .map(|(_, other)| PostLink::from(&posts[other]))
+                    .collect(),
+            }
+        })
+        .collect()
+}
+
+/// Weighs the words of each post by tf-idf, so words used by every post dont count.
+#[allow(clippy::cast_precision_loss)]
+fn term_vectors(posts: &[Post]) -> Vec<HashMap<String, f64>> {
+    let counts: Vec<HashMap<String, usize>> = posts
+        .iter()
+        .map(|post| {
+            let mut counts = HashMap::new();
+            for word in words(&post.title).chain(words(&post.contents)) {
+                *counts.entry(word).or_default() += 1;
+            }
+            counts
+        })
+        .collect();
+
+    let mut document_frequency: HashMap<&str, usize> = HashMap::new();
+    for words in &counts {
+        for word in words.keys() {
+            *document_frequency.entry(word).or_default() += 1;
+        }
+    }
+
+    let total = posts.len() as f64;
+    counts
+        .iter()
+        .map(|words| {
+            words
+                .iter()
+                .map(|(word, &count)| {
+                    let idf = (total / document_frequency[word.as_str()] as f64).ln();
+                    (word.clone(), count as f64 * idf)
+                })
+                .collect()
+        })
+        .collect()
+}
+
+fn cosine_similarity(a: &HashMap<String, f64>, b: &HashMap<String, f64>) -> f64 {
+    let dot: f64 = a
+        .iter()
+        .filter_map(|(word, weight)| b.get(word).map(|other| weight * other))
+        .sum();
+    let norm = |vector: &HashMap<String, f64>| vector.values().map(|w| w * w).sum::<f64>().sqrt();
+
+    let norms = norm(a) * norm(b);
+    if norms == 0.0 { 0.0 } else { dot / norms }
+}
+
+/// The lowercase words in `text`, skipping html tags and short words.
+fn words(text: &str) -> impl Iterator<Item = String> + '_ {
+    let mut in_tag = false;
+    text.split(move |c: char| {
+        match c {
+            '<' => in_tag = true,
+            '>' => in_tag = false,
+            _ => return in_tag || !c.is_alphanumeric(),
+        }
+        true
+    })
+    .filter(|word| word.chars().count() > 2)
+    .map(str::to_lowercase)
+}
@@ -0,0 +1,166 @@
+use std::{fmt::Write, fs, path::Path};
+
+use chrono::Datelike;
+use tracing::{Level, debug, info, span};
+
+use crate::{
+    archive::archive_years,
+    build_page::{Post, PostList},
+    config::Config,
+    error::{BuildError, IoResultExt},
+    tags::tag_url,
+    util::visit_dir,
+};
+
+/// A page listed in the sitemap.
+struct SitemapEntry {
+    /// The path of the page, relative to the base url
+    path: String,
+    lastmod: Option<String>,
+}
+
+/// Writes `sitemap.xml`, listing the homepage, every published post, every tag page and
+/// every html page in the static directory.
+pub(crate) fn output_sitemap_to_fs(blogs: &PostList, config: &Config) -> Result<(), BuildError> {
+    let span = span!(Level::INFO, "output sitemap");
+    let _enter = span.enter();
+
+    info!("creating sitemap");
+    let mut entries = vec![SitemapEntry {
+        path: "/".into(),
+        lastmod: newest(blogs.blogs.iter()),
+    }];
+
+    for post in &blogs.blogs {
+        entries.push(SitemapEntry {
+            path: format!("/posts/{}", post.slug),
+            lastmod: Some(post.metadata.lastmod().datetime().to_rfc3339()),
+        });
+    }
+
+    entries.push(SitemapEntry {
+        path: "/archive/".into(),
+        lastmod: newest(blogs.blogs.iter()),
+    });
+    if config.archive.year_pages {
+        for year in archive_years(blogs) {
+            let posts = blogs
+                .blogs
+                .iter()
+                .filter(|post| post.metadata.date.datetime().year() == year);
+            entries.push(SitemapEntry {
+                path: format!("/archive/{year}/"),
+                lastmod: newest(posts),
+            });
+        }
+    }
+
+    entries.push(SitemapEntry {
+        path: "/tags/".into(),
+        lastmod: newest(
+            blogs
+                .blogs
+                .iter()
+                .filter(|post| post.metadata.tags.is_some()),
+        ),
+    });
+
+    let mut tags: Vec<_> = blogs.tags.iter().collect();
+    tags.sort_by_key(|(tag, _)| *tag);
+    for (slug, tag) in tags {
+        let posts = blogs
+            .blogs
+            .iter()
+            .filter(|post| tag.posts.contains(&post.slug));
+        entries.push(SitemapEntry {
+            path: tag_url(slug),
+            lastmod: newest(posts),
+        });
+    }
+
+    entries.extend(static_pages(&config.paths.static_files)?);
+
+    let mut sitemap = String::from(concat!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+        "\n",
+        r#"<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">"#,
+        "\n"
+    ));
+    for entry in entries {
+        let _ = write!(
+            sitemap,
+            "  <url>\n    <loc>{}</loc>\n",
+            escape_xml(&format!("{}{}", config.site.base_url, entry.path))
+        );
+        if let Some(lastmod) = entry.lastmod {
+            let _ = writeln!(sitemap, "    <lastmod>{}</lastmod>", escape_xml(&lastmod));
+        }
+        sitemap.push_str("  </url>\n");
+    }
+    sitemap.push_str("</urlset>\n");
+
+    debug!("writing sitemap to fs");
+    let sitemap_file = config.paths.output.join("sitemap.xml");
+    fs::write(&sitemap_file, sitemap).with_path(&sitemap_file)
+}
+
+/// Writes `robots.txt`, pointing crawlers at the sitemap.
+pub(crate) fn output_robots_to_fs(config: &Config) -> Result<(), BuildError> {
+    info!("creating robots.txt");
+
+    let mut robots = String::from("User-agent: *\n");
+    if config.robots.disallow.is_empty() {
+        robots.push_str("Allow: /\n");
+    }
+    for path in &config.robots.disallow {
+        let _ = writeln!(robots, "Disallow: {path}");
+    }
+    let _ = writeln!(robots, "\nSitemap: {}/sitemap.xml", config.site.base_url);
+
+    let robots_file = config.paths.output.join("robots.txt");
+    fs::write(&robots_file, robots).with_path(&robots_file)
+}
+
+/// The most recent lastmod of `posts`.
+fn newest<'a>(posts: impl Iterator<Item = &'a Post>) -> Option<String> {
+    posts
+        .map(|post| post.metadata.lastmod())
+        .max()
+        .map(|date| date.datetime().to_rfc3339())
+}
+
+/// Finds every html file in the static directory, these are copied as-is so have no front matter.
+fn static_pages(static_dir: &Path) -> Result<Vec<SitemapEntry>, BuildError> {
+    let mut pages: Vec<_> = visit_dir(static_dir)
+        .with_path(static_dir)?
+        .into_iter()
+        .filter(|file| file.extension().is_some_and(|ext| ext == "html"))
+        .filter_map(|file| {
+            let relative = file
+                .strip_prefix(static_dir)
+                .ok()?
+                .to_str()?
+                .replace('\\', "/");
+            let path = match relative.strip_suffix("index.html") {
+                Some(dir) => format!("/{dir}"),
+                None => format!("/{relative}"),
+            };
+            Some(SitemapEntry {
+                path,
+                lastmod: None,
+            })
+        })
+        .collect();
+    pages.sort_by(|a, b| a.path.cmp(&b.path));
+
+    Ok(pages)
+}
+
+fn escape_xml(input: &str) -> String {
+    input
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
@@ -0,0 +1,116 @@
+use std::collections::HashSet;
+
+use serde::Serialize;
+use slugify::slugify;
+
+/// A tag and the slug of every post using it or one of its child tags.
+#[derive(Debug, Default)]
+pub struct TagPosts {
+    /// The name as written in the front matter, eg. `homelab/proxmox`
+    pub name: String,
+    pub posts: HashSet<String>,
+}
+
+/// A link to a tag page.
+#[derive(Serialize, Debug, Clone)]
+pub struct TagLink {
+    pub name: String,
+    pub url: String,
+}
+
+impl TagLink {
+    #[must_use]
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            url: tag_url(&tag_slug(name)),
+        }
+    }
+}
+
+/// Slugifies each level of a nested tag, `Home Lab/Proxmox` becomes `home-lab/proxmox`.
+#[must_use]
+pub fn tag_slug(tag: &str) -> String {
+    tag.trim_start_matches('#')
+        .split('/')
+        .map(|level| slugify!(level))
+        .filter(|level| !level.is_empty())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// The url of the tag page for a slug from [`tag_slug`].
+#[must_use]
+pub fn tag_url(slug: &str) -> String {
+    format!("/tags/{slug}/")
+}
+
+/// The tag and each of its parents, from the top level down.
+///
+/// `homelab/proxmox` gives `homelab` then `homelab/proxmox`.
+#[must_use]
+pub fn tag_levels(tag: &str) -> Vec<&str> {
+    let tag = tag.trim_start_matches('#').trim_matches('/');
+    tag.match_indices('/')
+        .map(|(index, _)| &tag[..index])
+        .chain([tag])
+        .filter(|level| !tag_slug(level).is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tag_slug_flat() {
+        assert_eq!(tag_slug("#linux"), "linux");
+        assert_eq!(tag_slug("Media Server"), "media-server");
+    }
+
+    #[test]
+    fn tag_slug_nested() {
+        assert_eq!(tag_slug("#Home Lab/Proxmox"), "home-lab/proxmox");
+        assert_eq!(tag_slug("a/b/c"), "a/b/c");
+    }
+
+    #[test]
+    fn tag_slug_skips_empty_levels() {
+        assert_eq!(tag_slug("/homelab//proxmox/"), "homelab/proxmox");
+        assert_eq!(tag_slug("homelab/!!!/proxmox"), "homelab/proxmox");
+        assert_eq!(tag_slug("#"), "");
+    }
+
+    #[test]
+    fn tag_url_from_slug() {
+        assert_eq!(tag_url("homelab/proxmox"), "/tags/homelab/proxmox/");
+        assert_eq!(TagLink::new("#Home Lab").url, "/tags/home-lab/");
+    }
+
+    #[test]
+    fn tag_levels_flat() {
+        assert_eq!(tag_levels("#linux"), vec!["linux"]);
+    }
+
+    #[test]
+    fn tag_levels_nested() {
+        assert_eq!(
+            tag_levels("#homelab/proxmox/lxc"),
+            vec!["homelab", "homelab/proxmox", "homelab/proxmox/lxc"]
+        );
+    }
+
+    #[test]
+    fn tag_levels_trims_slashes() {
+        assert_eq!(
+            tag_levels("/homelab/proxmox/"),
+            vec!["homelab", "homelab/proxmox"]
+        );
+    }
+
+    #[test]
+    fn tag_levels_skips_levels_without_a_slug() {
+        assert_eq!(tag_levels("!!!/proxmox"), vec!["!!!/proxmox"]);
+        assert!(tag_levels("#").is_empty());
+    }
+}
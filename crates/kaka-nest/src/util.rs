@@ -1,13 +1,10 @@
 use std::{
-    fs,
+    fs, io,
     path::{Path, PathBuf},
 };
 
-use color_eyre::eyre::Result;
-
-pub(crate) fn get_blog_paths() -> Result<Vec<PathBuf>> {
-    // TODO, move into an option in the config file
-    let files = visit_dir(Path::new("./assets/blog"))?
+pub(crate) fn get_blog_paths(content_dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut files: Vec<_> = visit_dir(content_dir)?
         .into_iter()
         .filter(|file| {
             file.extension()
@@ -15,10 +12,12 @@ pub(crate) fn get_blog_paths() -> Result<Vec<PathBuf>> {
                 .is_some_and(|ext| ext == "md")
         })
         .collect();
+    // Keep the build order stable between runs
+    files.sort();
     Ok(files)
 }
 
-pub(crate) fn visit_dir(dir: &Path) -> Result<Vec<PathBuf>> {
+pub(crate) fn visit_dir(dir: &Path) -> io::Result<Vec<PathBuf>> {
     let mut files: Vec<PathBuf> = Vec::new();
 
     for entry in fs::read_dir(dir)? {
@@ -36,3 +35,8 @@ pub(crate) fn visit_dir(dir: &Path) -> Result<Vec<PathBuf>> {
 
     Ok(files)
 }
+
+/// Converts a byte offset into `content` to a 1-based line number.
+pub(crate) fn line_of(content: &str, offset: usize) -> usize {
+    content[..offset].matches('\n').count() + 1
+}
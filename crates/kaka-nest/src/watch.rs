@@ -0,0 +1,91 @@
+use std::{
+    path::{Component, Path, PathBuf},
+    time::Duration,
+};
+
+use color_eyre::eyre::Result;
+use notify_debouncer_full::{
+    DebounceEventResult, Debouncer, RecommendedCache, new_debouncer,
+    notify::{EventKind, RecommendedWatcher, RecursiveMode},
+};
+use server_view::LiveReload;
+use tracing::{error, info};
+
+use crate::{
+    build_page::{BuildOptions, create_blog_on_system},
+    config::Config,
+};
+
+/// Rebuilds the site whenever the vault, templates or static files change,
+/// telling every open page to reload afterwards.
+///
+/// Watching stops when the returned [`Debouncer`] is dropped.
+///
+/// # Errors
+/// - Creating the file watcher
+/// - Watching any of the input directories
+pub fn watch_and_rebuild(
+    config: Config,
+    options: BuildOptions,
+    live_reload: LiveReload,
+) -> Result<Debouncer<RecommendedWatcher, RecommendedCache>> {
+    // Event paths are absolute, so these have to be too
+    let watched = [
+        config.paths.content.canonicalize()?,
+        config.paths.templates.canonicalize()?,
+        config.paths.static_files.canonicalize()?,
+    ];
+    let output = config.paths.output.canonicalize()?;
+    let watched_dirs = watched.clone();
+
+    let mut debouncer = new_debouncer(
+        Duration::from_millis(200),
+        None,
+        move |events: DebounceEventResult| {
+            let events = match events {
+                Ok(events) => events,
+                Err(errors) => {
+                    for err in errors {
+                        error!("file watcher failed: {err}");
+                    }
+                    return;
+                }
+            };
+
+            // Building reads every input file, so access events have to be ignored
+            let relevant = events
+                .iter()
+                .filter(|event| !matches!(event.kind, EventKind::Access(_)))
+                .flat_map(|event| &event.paths)
+                .any(|path| !path.starts_with(&output) && !is_hidden(path, &watched_dirs));
+            if !relevant {
+                return;
+            }
+
+            info!("files changed, rebuilding");
+            match create_blog_on_system(&config, &options) {
+                Ok(_) => live_reload.reload(),
+                Err(err) => error!("rebuild failed: {:?}", err.into_report()),
+            }
+        },
+    )?;
+
+    for dir in &watched {
+        info!("watching {}", dir.display());
+        debouncer.watch(dir, RecursiveMode::Recursive)?;
+    }
+
+    Ok(debouncer)
+}
+
+/// Obsidian keeps its workspace state in `.obsidian`, which changes constantly.
+fn is_hidden(path: &Path, watched: &[PathBuf]) -> bool {
+    let relative = watched
+        .iter()
+        .find_map(|dir| path.strip_prefix(dir).ok())
+        .unwrap_or(path);
+
+    relative.components().any(|component| {
+        matches!(component, Component::Normal(name) if name.to_string_lossy().starts_with('.'))
+    })
+}
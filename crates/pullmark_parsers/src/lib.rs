@@ -1,11 +1,23 @@
-use std::{str::FromStr, sync::LazyLock};
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashSet, VecDeque},
+    ops::{Range, RangeInclusive},
+    str::FromStr,
+    sync::LazyLock,
+};
 
-use handlebars::RenderError;
-use pulldown_cmark::{CodeBlockKind, Event, Tag, TagEnd};
-use serde::Serialize;
-use shared_utils::HANDLEBARS;
-use syntastica::{Processor, renderer::HtmlRenderer};
+use percent_encoding::{AsciiSet, CONTROLS, utf8_percent_encode};
+use pulldown_cmark::{CodeBlockKind, Event, LinkType, Tag, TagEnd};
+use serde::{Deserialize, Serialize};
+use shared_utils::{TemplateError, render_template};
+use slugify::slugify;
+use syntastica::{
+    Processor,
+    renderer::{HtmlRenderer, Renderer},
+    theme::{ResolvedTheme, THEME_KEYS},
+};
 use syntastica_parsers::{Lang, LanguageSetImpl};
+use thiserror::Error;
 use tracing::debug;
 
 pub static LEAKED_LANGSET: LazyLock<&'static LanguageSetImpl> =
@@ -17,52 +29,523 @@ thread_local! {
         std::cell::RefCell::new(Processor::new(*LEAKED_LANGSET));
 }
 
+/// Characters escaped in the file name of embedded images.
+const IMAGE_PATH: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?');
+
+/// File extensions which are embedded as images, other embeds are left alone.
+const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "gif", "webp", "svg", "avif", "bmp"];
+
+/// A pullmark event, with the byte range of the source it was parsed from.
+pub type OffsetEvent<'a> = (Event<'a>, Range<usize>);
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("failed to render the `{template}` template")]
+    Template {
+        template: &'static str,
+        offset: usize,
+        #[source]
+        source: TemplateError,
+    },
+}
+
+impl Error {
+    /// The byte offset into the markdown source this error was raised at.
+    pub fn offset(&self) -> usize {
+        match self {
+            Self::Template { offset, .. } => *offset,
+        }
+    }
+}
+
+/// A problem which didnt stop the markdown from rendering.
+#[derive(Debug, Error)]
+pub enum Warning {
+    #[error("failed to highlight `{lang}` codeblock, using plain code instead: {source}")]
+    Highlight {
+        lang: String,
+        offset: usize,
+        source: Box<syntastica::Error>,
+    },
+    #[error("wikilink `[[{target}]]` doesnt point to a published post")]
+    UnresolvedWikilink { target: String, offset: usize },
+    #[error("codeblock language `{lang}` has no tree-sitter parser, using plain code instead")]
+    UnknownLanguage { lang: String, offset: usize },
+}
+
+impl Warning {
+    /// The byte offset into the markdown source this warning was raised at.
+    pub fn offset(&self) -> usize {
+        match self {
+            Self::Highlight { offset, .. }
+            | Self::UnresolvedWikilink { offset, .. }
+            | Self::UnknownLanguage { offset, .. } => *offset,
+        }
+    }
+}
+
+/// Collects errors and warnings raised by the passes in this crate, as they
+/// cant be returned through [`Iterator::next`].
+#[derive(Debug, Default)]
+pub struct Diagnostics {
+    errors: RefCell<Vec<Error>>,
+    warnings: RefCell<Vec<Warning>>,
+}
+
+impl Diagnostics {
+    fn error(&self, error: Error) {
+        self.errors.borrow_mut().push(error);
+    }
+
+    fn warn(&self, warning: Warning) {
+        self.warnings.borrow_mut().push(warning);
+    }
+
+    pub fn into_parts(self) -> (Vec<Error>, Vec<Warning>) {
+        (self.errors.into_inner(), self.warnings.into_inner())
+    }
+}
+
 #[derive(Serialize)]
 struct CodeBlock {
     lang: String,
-    contents: String,
+    /// The file name from `title="main.rs"`
+    title: Option<String>,
+    linenos: bool,
+    lines: Vec<CodeLine>,
 }
 
 #[derive(Serialize)]
+struct CodeLine {
+    number: usize,
+    /// Highlighted html of the line, without a trailing newline
+    html: String,
+    highlighted: bool,
+}
+
+/// The info string after the opening fence of a codeblock:
+///
+/// ~~~md
+/// ```rust title="main.rs" {3,5-7} linenos
+/// ~~~
+///
+/// Everything but the language is optional and can be in any order, unknown options are ignored.
+#[derive(Debug, Default)]
+pub struct FenceInfo {
+    pub lang: Option<String>,
+    pub title: Option<String>,
+    /// 1-based line numbers to highlight
+    pub highlight: Vec<RangeInclusive<usize>>,
+    pub linenos: bool,
+}
+
+impl FenceInfo {
+    pub fn parse(info: &str) -> Self {
+        let mut fence = Self::default();
+        for (index, token) in split_fence_info(info).into_iter().enumerate() {
+            if let Some(ranges) = token.strip_prefix('{').and_then(|t| t.strip_suffix('}')) {
+                fence
+                    .highlight
+                    .extend(ranges.split(',').filter_map(parse_line_range));
+            } else if let Some((key, value)) = token.split_once('=') {
+                if key == "title" {
+                    fence.title = Some(value.trim_matches('"').to_string());
+                }
+            } else if token == "linenos" {
+                fence.linenos = true;
+            } else if index == 0 {
+                fence.lang = Some(token);
+            }
+        }
+        fence
+    }
+
+    fn is_highlighted(&self, line: usize) -> bool {
+        self.highlight.iter().any(|range| range.contains(&line))
+    }
+}
+
+/// Splits on whitespace outside of double quotes and braces, so titles can contain spaces
+/// and line ranges can be written like `{3, 5-7}`.
+fn split_fence_info(info: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut token = String::new();
+    let mut quoted = false;
+    let mut braced = false;
+    for char in info.chars() {
+        match char {
+            '"' => {
+                quoted = !quoted;
+                token.push(char);
+            }
+            '{' | '}' if !quoted => {
+                braced = char == '{';
+                token.push(char);
+            }
+            char if char.is_whitespace() && !quoted && !braced => {
+                if !token.is_empty() {
+                    tokens.push(std::mem::take(&mut token));
+                }
+            }
+            char => token.push(char),
+        }
+    }
+    if !token.is_empty() {
+        tokens.push(token);
+    }
+    tokens
+}
+
+/// Parses `3` or `5-7`, a reversed range like `7-5` is read as `5-7`.
+fn parse_line_range(range: &str) -> Option<RangeInclusive<usize>> {
+    match range.trim().split_once('-') {
+        Some((start, end)) => {
+            let start: usize = start.trim().parse().ok()?;
+            let end: usize = end.trim().parse().ok()?;
+            Some(start.min(end)..=start.max(end))
+        }
+        None => {
+            let line = range.trim().parse().ok()?;
+            Some(line..=line)
+        }
+    }
+}
+
+/// Every Obsidian callout type, aliases like `faq` map onto the type they share a style with.
+#[derive(Serialize, Debug, Clone, Copy)]
 #[serde(rename_all = "lowercase")]
 enum BlockquoteTypes {
+    Note,
+    Abstract,
+    Info,
+    Todo,
+    Tip,
+    Success,
     Question,
-    // Aha,
+    Warning,
+    Failure,
+    Danger,
+    Bug,
+    Example,
+    Quote,
+}
+
+impl BlockquoteTypes {
+    fn from_marker(marker: &str) -> Option<Self> {
+        let blockquote_type = match marker.to_lowercase().as_str() {
+            "note" => Self::Note,
+            "abstract" | "summary" | "tldr" => Self::Abstract,
+            "info" => Self::Info,
+            "todo" => Self::Todo,
+            "tip" | "hint" | "important" => Self::Tip,
+            "success" | "check" | "done" => Self::Success,
+            "question" | "help" | "faq" => Self::Question,
+            "warning" | "caution" | "attention" => Self::Warning,
+            "failure" | "fail" | "missing" => Self::Failure,
+            "danger" | "error" => Self::Danger,
+            "bug" => Self::Bug,
+            "example" => Self::Example,
+            "quote" | "cite" => Self::Quote,
+            _ => return None,
+        };
+        Some(blockquote_type)
+    }
 }
 
 #[derive(Serialize)]
-struct BlockQuote<'a> {
-    blockquote_type: Option<BlockquoteTypes>,
-    contents: &'a str,
+struct BlockQuote {
+    blockquote_type: BlockquoteTypes,
+    /// Rendered html of the title
+    title: String,
+    /// `+` and `-` callouts can be folded, `-` callouts start folded
+    foldable: bool,
+    open: bool,
+    contents: String,
+}
+
+/// Languages which are meant to be shown as plain code, so they arent warned about.
+const PLAIN_TEXT_LANGUAGES: &[&str] = &["text", "txt", "plain", "plaintext"];
+
+/// Common names for languages which syntastica only knows by another name.
+const DEFAULT_LANGUAGE_ALIASES: &[(&str, &str)] = &[
+    ("rs", "rust"),
+    ("sh", "bash"),
+    ("shell", "bash"),
+    ("zsh", "bash"),
+    ("console", "bash"),
+    ("shell-session", "bash"),
+    ("yml", "yaml"),
+    ("js", "javascript"),
+    ("mjs", "javascript"),
+    ("cjs", "javascript"),
+    ("jsx", "javascript"),
+    ("ts", "typescript"),
+    ("py", "python"),
+    ("c++", "cpp"),
+    ("cxx", "cpp"),
+    ("cc", "cpp"),
+    ("hpp", "cpp"),
+    ("h", "c"),
+    ("golang", "go"),
+];
+
+/// Maps the language written after a codeblock fence to a tree-sitter parser.
+///
+/// Names are matched case-insensitively, first against the aliases it was created with,
+/// then the built in aliases like `rs` and `yml`, then the parser names themselves. An alias
+/// can point at a built in alias, so `dockerfile = "sh"` works.
+#[derive(Debug, Clone, Default)]
+pub struct Languages {
+    aliases: BTreeMap<String, String>,
+}
+
+impl Languages {
+    /// Adds `aliases` from a name to a parser name, on top of the built in ones.
+    pub fn new(aliases: &BTreeMap<String, String>) -> Self {
+        Self {
+            aliases: aliases
+                .iter()
+                .map(|(alias, lang)| (alias.to_lowercase(), lang.to_lowercase()))
+                .collect(),
+        }
+    }
+
+    /// The name of the parser used for `lang`, or `None` if it cant be highlighted.
+    pub fn canonical(&self, lang: &str) -> Option<&'static str> {
+        self.resolve(lang).map(Into::into)
+    }
+
+    /// Returns true if codeblocks written in `lang` can be highlighted, or are plain text.
+    pub fn is_known(&self, lang: &str) -> bool {
+        self.is_plain(lang) || self.resolve(lang).is_some()
+    }
+
+    /// Returns true if `lang` is plain text like `txt`, which is never highlighted.
+    pub fn is_plain(&self, lang: &str) -> bool {
+        PLAIN_TEXT_LANGUAGES.contains(&self.name(lang).as_str())
+    }
+
+    fn resolve(&self, lang: &str) -> Option<Lang> {
+        Lang::from_str(&self.name(lang)).ok()
+    }
+
+    /// The lowercase name `lang` is an alias of, or `lang` itself.
+    fn name(&self, lang: &str) -> String {
+        let lang = lang.to_lowercase();
+        let lang = self.aliases.get(&lang).unwrap_or(&lang);
+        DEFAULT_LANGUAGE_ALIASES
+            .iter()
+            .find(|(alias, _)| alias == lang)
+            .map_or(lang.as_str(), |(_, name)| name)
+            .to_string()
+    }
+}
+
+/// Returns true if `marker` is a callout type [`format_blockquotes`] understands.
+pub fn is_known_callout(marker: &str) -> bool {
+    BlockquoteTypes::from_marker(marker).is_some()
+}
+
+/// How highlighted code is colored.
+#[derive(Debug, Clone)]
+pub enum HighlightStyle {
+    /// Every token gets an inline `style` from the theme
+    Inline(ResolvedTheme),
+    /// Tokens get a `hl-*` class for their highlight group, styled by [`theme_stylesheet`]
+    Classes,
+}
+
+impl HighlightStyle {
+    /// Colors tokens inline with `theme`, returning `None` if it isnt a syntastica theme.
+    pub fn inline(theme: &str) -> Option<Self> {
+        syntastica_themes::from_str(theme).map(Self::Inline)
+    }
+}
+
+/// Returns true if `theme` is in the syntastica theme set, eg. `one::dark`.
+pub fn is_known_theme(theme: &str) -> bool {
+    syntastica_themes::THEMES.contains(&theme)
+}
+
+/// Generates the css for [`HighlightStyle::Classes`], `light` is used instead of `dark`
+/// when the reader prefers a light color scheme.
+///
+/// Returns `None` if either theme isnt a syntastica theme.
+pub fn theme_stylesheet(dark: &str, light: Option<&str>) -> Option<String> {
+    let mut css = theme_rules(&syntastica_themes::from_str(dark)?, "");
+    if let Some(light) = light {
+        let rules = theme_rules(&syntastica_themes::from_str(light)?, "  ");
+        css.push_str(&format!(
+            "\n@media (prefers-color-scheme: light) {{\n{rules}}}\n"
+        ));
+    }
+    Some(css)
+}
+
+/// A rule for the codeblock and every highlight group the theme has a style for.
+fn theme_rules(theme: &ResolvedTheme, indent: &str) -> String {
+    let mut css = String::new();
+    if let Some(fg) = theme.fg() {
+        let (r, g, b) = fg.into_components();
+        let mut declarations = format!("color: rgb({r}, {g}, {b});");
+        if let Some(bg) = theme.bg() {
+            let (r, g, b) = bg.into_components();
+            declarations.push_str(&format!(" background-color: rgb({r}, {g}, {b});"));
+        }
+        css.push_str(&format!("{indent}.codeblock {{ {declarations} }}\n"));
+    }
+
+    for key in THEME_KEYS {
+        let Some(style) = theme.find_style(key) else {
+            continue;
+        };
+        let (r, g, b) = style.color().into_components();
+        let mut declarations = format!("color: rgb({r}, {g}, {b});");
+        if style.bold() {
+            declarations.push_str(" font-weight: bold;");
+        }
+        if style.italic() {
+            declarations.push_str(" font-style: italic;");
+        }
+        match (style.underline(), style.strikethrough()) {
+            (true, true) => declarations.push_str(" text-decoration: underline line-through;"),
+            (true, false) => declarations.push_str(" text-decoration: underline;"),
+            (false, true) => declarations.push_str(" text-decoration: line-through;"),
+            (false, false) => {}
+        }
+        css.push_str(&format!(
+            "{indent}.{} {{ {declarations} }}\n",
+            highlight_class(key)
+        ));
+    }
+    css
+}
+
+/// The class for a highlight group, `keyword.function` becomes `hl-keyword-function`.
+fn highlight_class(key: &str) -> String {
+    let key: String = key
+        .chars()
+        .map(|char| {
+            if char.is_ascii_alphanumeric() {
+                char
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    format!("hl-{key}")
 }
 
 /// Gets every codeblock in a pullmark parser and adds syntax highlighting to the html
-// ...existing code...
 pub fn highlight_codeblocks<'a, I>(
     parser: I,
     processer: &'a mut Processor<'static, LanguageSetImpl>,
-) -> impl Iterator<Item = Event<'a>> + 'a
+    style: &'a HighlightStyle,
+    languages: &'a Languages,
+    diagnostics: &'a Diagnostics,
+) -> impl Iterator<Item = OffsetEvent<'a>> + 'a
 where
-    I: Iterator<Item = Event<'a>> + 'a,
+    I: Iterator<Item = OffsetEvent<'a>> + 'a,
 {
-    struct HighlightCodeblocks<'a, I: Iterator<Item = Event<'a>>> {
+    struct HighlightCodeblocks<'a, I: Iterator<Item = OffsetEvent<'a>>> {
         inner: I,
         in_codeblock: bool,
-        code_lang: Option<String>,
+        code_info: String,
         code_buffer: String,
+        code_start: usize,
         processer: &'a mut Processor<'static, LanguageSetImpl>,
+        style: &'a HighlightStyle,
+        languages: &'a Languages,
+        diagnostics: &'a Diagnostics,
+    }
+
+    impl<'a, I: Iterator<Item = OffsetEvent<'a>>> HighlightCodeblocks<'a, I> {
+        fn highlight(&mut self) -> Result<String, Error> {
+            let info = FenceInfo::parse(&self.code_info);
+            let lines = self.highlight_lines(info.lang.as_deref());
+
+            // If Handlebar render is expensive, consider a simple format! here instead.
+            format_codeblock_html(&info, lines, self.code_start)
+        }
+
+        /// The html of each line of the codeblock, plain code if it cant be highlighted.
+        fn highlight_lines(&mut self, lang: Option<&str>) -> Vec<String> {
+            let plain = || self.code_buffer.lines().map(escape_html).collect();
+
+            let Some(lang) = lang else {
+                return plain();
+            };
+            if self.languages.is_plain(lang) {
+                return plain();
+            }
+            let Some(syntax) = self.languages.resolve(lang) else {
+                self.diagnostics.warn(Warning::UnknownLanguage {
+                    lang: lang.to_string(),
+                    offset: self.code_start,
+                });
+                return plain();
+            };
+
+            let mut processed = match self.processer.process(&self.code_buffer, syntax) {
+                Ok(processed) => processed,
+                Err(source) => {
+                    self.diagnostics.warn(Warning::Highlight {
+                        lang: lang.to_string(),
+                        offset: self.code_start,
+                        source: Box::new(source),
+                    });
+                    return plain();
+                }
+            };
+            if self.code_buffer.ends_with('\n') {
+                processed.pop();
+            }
+
+            // Each line is rendered on its own, so lines can be numbered and highlighted
+            processed
+                .into_iter()
+                .map(|line| match self.style {
+                    HighlightStyle::Inline(theme) => {
+                        syntastica::render(&vec![line], &mut HtmlRenderer, theme)
+                    }
+                    HighlightStyle::Classes => line
+                        .into_iter()
+                        .map(|(text, key)| {
+                            let text = HtmlRenderer.escape(text);
+                            match key {
+                                Some(key) => {
+                                    format!(
+                                        r#"<span class="{}">{text}</span>"#,
+                                        highlight_class(key)
+                                    )
+                                }
+                                None => text.into_owned(),
+                            }
+                        })
+                        .collect(),
+                })
+                .collect()
+        }
     }
 
-    impl<'a, I: Iterator<Item = Event<'a>>> Iterator for HighlightCodeblocks<'a, I> {
-        type Item = Event<'a>;
+    impl<'a, I: Iterator<Item = OffsetEvent<'a>>> Iterator for HighlightCodeblocks<'a, I> {
+        type Item = OffsetEvent<'a>;
 
         fn next(&mut self) -> Option<Self::Item> {
-            for event in self.inner.by_ref() {
+            while let Some((event, range)) = self.inner.next() {
                 match event {
                     Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(lang))) => {
                         self.in_codeblock = true;
-                        self.code_lang = Some(lang.to_string());
+                        self.code_info = lang.to_string();
                         self.code_buffer.clear();
+                        self.code_start = range.start;
                     }
                     Event::Text(text) if self.in_codeblock => {
                         self.code_buffer.push_str(&text);
@@ -70,35 +553,17 @@ where
                     Event::End(TagEnd::CodeBlock) if self.in_codeblock => {
                         self.in_codeblock = false;
 
-                        let highlighted_code = if let Some(lang) = self.code_lang.as_deref() {
-                            if let Ok(syntax) = Lang::from_str(lang) {
-                                let Some(processed) =
-                                    self.processer.process(&self.code_buffer, syntax).ok()
-                                else {
-                                    eprintln!("Highlighting code failed");
-                                    std::process::exit(0);
-                                };
-
-                                let highlighted = syntastica::render(
-                                    &processed,
-                                    &mut HtmlRenderer,
-                                    syntastica_themes::one::dark(),
-                                );
-
-                                // If Handlebar render is expensive, consider a simple format! here instead.
-                                format_codeblock_html(&highlighted, Some(lang)).ok()
-                            } else {
-                                format_codeblock_html(&self.code_buffer, None).ok()
+                        match self.highlight() {
+                            Ok(html) => {
+                                return Some((
+                                    Event::Html(html.into()),
+                                    self.code_start..range.end,
+                                ));
                             }
-                        } else {
-                            format_codeblock_html(&self.code_buffer, None).ok()
-                        };
-
-                        return Some(Event::Html(
-                            highlighted_code.expect("failed to highlight code").into(),
-                        ));
+                            Err(err) => self.diagnostics.error(err),
+                        }
                     }
-                    other => return Some(other),
+                    other => return Some((other, range)),
                 }
             }
             None
@@ -108,110 +573,844 @@ where
     HighlightCodeblocks {
         inner: parser,
         in_codeblock: false,
-        code_lang: None,
+        code_info: String::new(),
         code_buffer: String::with_capacity(1024 * 4),
+        code_start: 0,
         processer,
+        style,
+        languages,
+        diagnostics,
+    }
+}
+
+/// Points wikilinks like `[[Post#Section|alias]]` at the page of the post, `published` holds
+/// the slug of every post which can be linked to.
+///
+/// Links to unknown posts are replaced with their text.
+pub fn resolve_wikilinks<'a>(
+    parser: impl Iterator<Item = OffsetEvent<'a>>,
+    published: &'a HashSet<String>,
+    diagnostics: &'a Diagnostics,
+) -> impl Iterator<Item = OffsetEvent<'a>> {
+    struct ResolveWikilinks<'a, I: Iterator<Item = OffsetEvent<'a>>> {
+        inner: I,
+        in_unresolved: bool,
+        published: &'a HashSet<String>,
+        diagnostics: &'a Diagnostics,
+    }
+
+    impl<'a, I: Iterator<Item = OffsetEvent<'a>>> Iterator for ResolveWikilinks<'a, I> {
+        type Item = OffsetEvent<'a>;
+
+        fn next(&mut self) -> Option<Self::Item> {
+            for (event, range) in self.inner.by_ref() {
+                match event {
+                    Event::Start(Tag::Link {
+                        link_type: link_type @ LinkType::WikiLink { .. },
+                        dest_url,
+                        title,
+                        id,
+                    }) => {
+                        let (target, heading) = match dest_url.split_once('#') {
+                            Some((target, heading)) => (target, Some(heading)),
+                            None => (dest_url.as_ref(), None),
+                        };
+                        let anchor = heading.map(|heading| format!("#{}", slugify!(heading)));
+
+                        let url = if target.is_empty() {
+                            anchor.unwrap_or_default()
+                        } else {
+                            let slug = slugify!(target);
+                            if !self.published.contains(&slug) {
+                                self.diagnostics.warn(Warning::UnresolvedWikilink {
+                                    target: dest_url.to_string(),
+                                    offset: range.start,
+                                });
+                                self.in_unresolved = true;
+                                continue;
+                            }
+                            format!("/posts/{slug}{}", anchor.unwrap_or_default())
+                        };
+
+                        let link = Tag::Link {
+                            link_type,
+                            dest_url: url.into(),
+                            title,
+                            id,
+                        };
+                        return Some((Event::Start(link), range));
+                    }
+                    Event::End(TagEnd::Link) if self.in_unresolved => {
+                        self.in_unresolved = false;
+                    }
+                    _ => return Some((event, range)),
+                }
+            }
+            None
+        }
+    }
+
+    ResolveWikilinks {
+        inner: parser,
+        in_unresolved: false,
+        published,
+        diagnostics,
     }
 }
 
-/// Processes blockquotes with the format:
+/// Turns Obsidian image embeds into `<img>` tags pointing at `/images/`.
 ///
 /// ```md
-/// > [!question]
-/// > What is the meaning of life?
+/// ![[movie library setup.png]]
+/// ![[image.png|300]]
+/// ![[image.png|A caption|300x200]]
 /// ```
-pub fn format_blockquotes<'a>(
-    parser: impl Iterator<Item = Event<'a>>,
-) -> impl Iterator<Item = Event<'a>> {
-    struct FormatBlockquotes<'a, I: Iterator<Item = Event<'a>>> {
+///
+/// The size is a width, or `width`x`height`. The alt text is the caption if there is one,
+/// otherwise the file name without its extension.
+pub fn embed_images<'a>(
+    parser: impl Iterator<Item = OffsetEvent<'a>>,
+) -> impl Iterator<Item = OffsetEvent<'a>> {
+    struct EmbedImages<I> {
         inner: I,
-        in_blockquote: bool,
-        blockquote_buffer: String,
     }
 
-    impl<'a, I: Iterator<Item = Event<'a>>> Iterator for FormatBlockquotes<'a, I> {
-        type Item = Event<'a>;
+    impl<'a, I: Iterator<Item = OffsetEvent<'a>>> Iterator for EmbedImages<I> {
+        type Item = OffsetEvent<'a>;
 
         fn next(&mut self) -> Option<Self::Item> {
-            for event in self.inner.by_ref() {
+            let (event, range) = self.inner.next()?;
+
+            let Event::Start(Tag::Image {
+                link_type: LinkType::WikiLink { has_pothole },
+                dest_url,
+                ..
+            }) = &event
+            else {
+                return Some((event, range));
+            };
+            let is_image = dest_url
+                .rsplit_once('.')
+                .is_some_and(|(_, ext)| IMAGE_EXTENSIONS.contains(&ext.to_lowercase().as_str()));
+            if !is_image {
+                return Some((event, range));
+            }
+
+            let mut pothole = String::new();
+            let mut end = range.end;
+            for (event, range) in self.inner.by_ref() {
                 match event {
-                    Event::Start(Tag::BlockQuote(_)) => {
-                        self.in_blockquote = true;
-                        self.blockquote_buffer.clear();
-                    }
-                    Event::Text(text) if self.in_blockquote => {
-                        self.blockquote_buffer.push_str(&text);
-                        self.blockquote_buffer.push('\n'); // preserve line breaks
+                    Event::Text(text) => pothole.push_str(&text),
+                    Event::End(TagEnd::Image) => {
+                        end = range.end;
+                        break;
                     }
-                    Event::End(TagEnd::BlockQuote(_)) if self.in_blockquote => {
-                        self.in_blockquote = false;
-
-                        if let Some((blockquote_string, rest)) =
-                            parse_marker(&self.blockquote_buffer)
-                        {
-                            let blockquote_type = match blockquote_string.as_str() {
-                                "question" => Some(BlockquoteTypes::Question),
-                                // Add more types as needed
-                                _ => None,
-                            };
-
-                            let rendered_contents = HANDLEBARS
-                                .render(
-                                    "blockquote",
-                                    &BlockQuote {
-                                        blockquote_type,
-                                        contents: &rest,
-                                    },
-                                )
-                                .expect("Failed to render blockquote");
-                            return Some(Event::Html(rendered_contents.into()));
-                        }
-                        debug!("You forgot to add a type to blockquote");
+                    _ => {}
+                }
+            }
+            if !has_pothole {
+                pothole.clear();
+            }
+
+            let html = format_image_embed(dest_url, &pothole);
+            Some((Event::InlineHtml(html.into()), range.start..end))
+        }
+    }
+
+    EmbedImages { inner: parser }
+}
+
+fn format_image_embed(file_name: &str, pothole: &str) -> String {
+    let (caption, size) = match pothole.rsplit_once('|') {
+        Some((caption, size)) => (Some(caption), parse_image_size(size)),
+        None => match parse_image_size(pothole) {
+            Some(size) => (None, Some(size)),
+            None => (Some(pothole), None),
+        },
+    };
+
+    let alt = caption
+        .map(str::trim)
+        .filter(|caption| !caption.is_empty())
+        .unwrap_or_else(|| {
+            file_name
+                .rsplit_once('.')
+                .map_or(file_name, |(stem, _)| stem)
+        });
+
+    let mut html = format!(
+        r#"<img src="/images/{}" alt="{}""#,
+        utf8_percent_encode(file_name, IMAGE_PATH),
+        escape_html(alt)
+    );
+    if let Some((width, height)) = size {
+        html.push_str(&format!(r#" width="{width}""#));
+        if let Some(height) = height {
+            html.push_str(&format!(r#" height="{height}""#));
+        }
+    }
+    html.push_str(" />");
+
+    html
+}
+
+/// Parses `300` or `300x200`.
+fn parse_image_size(size: &str) -> Option<(u32, Option<u32>)> {
+    match size.trim().split_once('x') {
+        Some((width, height)) => Some((width.parse().ok()?, Some(height.parse().ok()?))),
+        None => Some((size.trim().parse().ok()?, None)),
+    }
+}
+
+/// A heading listed in the table of contents, with the headings nested under it.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TocEntry {
+    pub level: u8,
+    /// The id of the heading, which is also its wikilink anchor
+    pub id: String,
+    pub title: String,
+    pub children: Vec<TocEntry>,
+}
+
+/// Collects the headings found by [`anchor_headings`], as they cant be returned through
+/// [`Iterator::next`].
+#[derive(Debug, Default)]
+pub struct TableOfContents {
+    headings: RefCell<Vec<TocEntry>>,
+}
+
+impl TableOfContents {
+    fn push(&self, level: u8, id: String, title: String) {
+        self.headings.borrow_mut().push(TocEntry {
+            level,
+            id,
+            title,
+            children: Vec::new(),
+        });
+    }
+
+    /// Nests each heading under the closest heading before it with a lower level.
+    pub fn into_entries(self) -> Vec<TocEntry> {
+        fn nest(
+            headings: &mut std::iter::Peekable<std::vec::IntoIter<TocEntry>>,
+            level: u8,
+        ) -> Vec<TocEntry> {
+            let mut entries = Vec::new();
+            while let Some(mut entry) = headings.next_if(|heading| heading.level > level) {
+                entry.children = nest(headings, entry.level);
+                entries.push(entry);
+            }
+            entries
+        }
+
+        nest(&mut self.headings.into_inner().into_iter().peekable(), 0)
+    }
+}
+
+/// Gives every heading an id and a permalink, and adds it to `toc`.
+///
+/// Ids are the slug of the heading text, the same anchor wikilinks like `[[Post#Heading]]`
+/// point at. Repeated headings get a `-1`, `-2`, ... suffix.
+pub fn anchor_headings<'a>(
+    parser: impl Iterator<Item = OffsetEvent<'a>>,
+    toc: &'a TableOfContents,
+) -> impl Iterator<Item = OffsetEvent<'a>> {
+    struct AnchorHeadings<'a, I> {
+        inner: I,
+        /// The rest of the current heading, ending with its permalink
+        pending: VecDeque<OffsetEvent<'a>>,
+        used: HashSet<String>,
+        toc: &'a TableOfContents,
+    }
+
+    impl<'a, I: Iterator<Item = OffsetEvent<'a>>> Iterator for AnchorHeadings<'a, I> {
+        type Item = OffsetEvent<'a>;
+
+        fn next(&mut self) -> Option<Self::Item> {
+            if let Some(pending) = self.pending.pop_front() {
+                return Some(pending);
+            }
+
+            let (event, range) = self.inner.next()?;
+            let Event::Start(Tag::Heading {
+                level,
+                id,
+                classes,
+                attrs,
+            }) = event
+            else {
+                return Some((event, range));
+            };
+
+            let mut title = String::new();
+            for (event, range) in self.inner.by_ref() {
+                match &event {
+                    Event::Text(text) | Event::Code(text) => title.push_str(text),
+                    Event::End(TagEnd::Heading(_)) => {
+                        self.pending.push_back((event, range));
+                        break;
                     }
-                    _ => return Some(event),
+                    _ => {}
                 }
+                self.pending.push_back((event, range));
             }
-            None
+
+            let id = id.map_or_else(|| self.unique_slug(&title), |id| id.to_string());
+            let permalink = format!(
+                r##"<a class="heading-anchor" href="#{id}" aria-label="Link to this section">#</a>"##
+            );
+            let end = self.pending.len().saturating_sub(1);
+            self.pending
+                .insert(end, (Event::InlineHtml(permalink.into()), range.clone()));
+
+            self.toc
+                .push(level as u8, id.clone(), title.trim().to_string());
+            let heading = Tag::Heading {
+                level,
+                id: Some(id.into()),
+                classes,
+                attrs,
+            };
+            Some((Event::Start(heading), range))
+        }
+    }
+
+    impl<'a, I> AnchorHeadings<'a, I> {
+        fn unique_slug(&mut self, title: &str) -> String {
+            let slug = slugify!(title);
+            let slug = if slug.is_empty() {
+                "section".to_string()
+            } else {
+                slug
+            };
+
+            let mut unique = slug.clone();
+            let mut suffix = 0;
+            while !self.used.insert(unique.clone()) {
+                suffix += 1;
+                unique = format!("{slug}-{suffix}");
+            }
+            unique
         }
     }
 
+    AnchorHeadings {
+        inner: parser,
+        pending: VecDeque::new(),
+        used: HashSet::new(),
+        toc,
+    }
+}
+
+/// Renders Obsidian callouts with the `blockquote` template:
+///
+/// ```md
+/// > [!question]- An optional title
+/// > What is the meaning of life?
+/// ```
+///
+/// The body of the callout can contain any markdown, including other callouts.
+/// Blockquotes without a marker are left alone, unknown types are rendered as notes.
+pub fn format_blockquotes<'a>(
+    parser: impl Iterator<Item = OffsetEvent<'a>>,
+    diagnostics: &'a Diagnostics,
+) -> impl Iterator<Item = OffsetEvent<'a>> {
     FormatBlockquotes {
         inner: parser,
-        in_blockquote: false,
-        blockquote_buffer: String::with_capacity(4 * 1024),
+        pending: VecDeque::new(),
+        diagnostics,
     }
 }
 
-fn parse_marker(input: &str) -> Option<(String, String)> {
-    let mut lines = input.lines();
+struct FormatBlockquotes<'a, I: Iterator<Item = OffsetEvent<'a>>> {
+    inner: I,
+    /// Events of a plain blockquote, which are returned unchanged
+    pending: VecDeque<OffsetEvent<'a>>,
+    diagnostics: &'a Diagnostics,
+}
 
-    // Expect: line 1 == "[", line 2 starts with "!", line 3 == "]"
-    if lines.next()? != "[" {
-        return None;
+impl<'a, I: Iterator<Item = OffsetEvent<'a>>> Iterator for FormatBlockquotes<'a, I> {
+    type Item = OffsetEvent<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(pending) = self.pending.pop_front() {
+            return Some(pending);
+        }
+
+        let (event, range) = self.inner.next()?;
+        if !matches!(event, Event::Start(Tag::BlockQuote(_))) {
+            return Some((event, range));
+        }
+
+        // Collect the whole blockquote, so callouts nested inside it can be formatted first
+        let mut depth = 1;
+        let mut events = Vec::new();
+        let mut end = None;
+        for (event, range) in self.inner.by_ref() {
+            match event {
+                Event::Start(Tag::BlockQuote(_)) => depth += 1,
+                Event::End(TagEnd::BlockQuote(_)) => depth -= 1,
+                _ => {}
+            }
+            if depth == 0 {
+                end = Some((event, range));
+                break;
+            }
+            events.push((event, range));
+        }
+
+        let events: Vec<_> = FormatBlockquotes {
+            inner: events.into_iter(),
+            pending: VecDeque::new(),
+            diagnostics: self.diagnostics,
+        }
+        .collect();
+
+        let Some(callout) = parse_callout(&events) else {
+            debug!("blockquote has no callout marker");
+            self.pending.extend(events);
+            self.pending.extend(end);
+            return Some((event, range));
+        };
+
+        let offset = range.start;
+        let range = range.start..end.map_or(range.end, |(_, range)| range.end);
+        match render_template("blockquote", &callout) {
+            Ok(html) => Some((Event::Html(html.into()), range)),
+            Err(source) => {
+                self.diagnostics.error(Error::Template {
+                    template: "blockquote",
+                    offset,
+                    source,
+                });
+                self.next()
+            }
+        }
     }
+}
 
-    let marker_line = lines.next()?;
-    if !marker_line.starts_with('!') {
+/// Parses the events inside a blockquote as a callout, returning `None` if it has no marker.
+fn parse_callout(events: &[OffsetEvent]) -> Option<BlockQuote> {
+    let (Event::Start(Tag::Paragraph), _) = events.first()? else {
         return None;
+    };
+
+    // The marker is split across several text events, eg. `[`, `!question`, `]-`, ` Title`
+    let mut head = String::new();
+    let mut rest = events[1..].iter().peekable();
+    while let Some((Event::Text(text), _)) = rest.peek() {
+        head.push_str(text);
+        rest.next();
     }
-    let marker = marker_line.strip_prefix('!')?.to_string();
 
-    if lines.next()? != "]" {
-        return None;
+    let (marker, title_start) = head.strip_prefix("[!")?.split_once(']')?;
+    let blockquote_type = BlockquoteTypes::from_marker(marker).unwrap_or(BlockquoteTypes::Note);
+    let (foldable, open, title_start) = match title_start.chars().next() {
+        Some('+') => (true, true, &title_start[1..]),
+        Some('-') => (true, false, &title_start[1..]),
+        _ => (false, true, title_start),
+    };
+
+    // The title is the rest of the first line
+    let mut title_events = vec![Event::Text(title_start.trim_start().to_string().into())];
+    let mut body = Vec::new();
+    for (event, _) in rest.by_ref() {
+        match event {
+            Event::SoftBreak | Event::HardBreak => {
+                body.push(Event::Start(Tag::Paragraph));
+                break;
+            }
+            Event::End(TagEnd::Paragraph) => break,
+            event => title_events.push(event.clone()),
+        }
+    }
+    body.extend(rest.map(|(event, _)| event.clone()));
+
+    let mut title = String::new();
+    pulldown_cmark::html::push_html(&mut title, title_events.into_iter());
+    if title.trim().is_empty() {
+        let mut chars = marker.chars();
+        title = chars
+            .next()
+            .map(|first| first.to_uppercase().chain(chars).collect())
+            .unwrap_or_default();
     }
 
-    // Collect the rest of the lines as the "value"
-    let value = lines.collect::<Vec<_>>().join("\n");
+    let mut contents = String::new();
+    pulldown_cmark::html::push_html(&mut contents, body.into_iter());
+
+    Some(BlockQuote {
+        blockquote_type,
+        title,
+        foldable,
+        open,
+        contents,
+    })
+}
 
-    Some((marker, value))
+fn escape_html(input: &str) -> String {
+    let mut escaped = String::with_capacity(input.len());
+    for char in input.chars() {
+        match char {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(char),
+        }
+    }
+    escaped
 }
 
-fn format_codeblock_html(input: &str, lang: Option<&str>) -> Result<String, RenderError> {
-    HANDLEBARS.render(
+fn format_codeblock_html(
+    info: &FenceInfo,
+    lines: Vec<String>,
+    offset: usize,
+) -> Result<String, Error> {
+    let lines = lines
+        .into_iter()
+        .enumerate()
+        .map(|(index, html)| CodeLine {
+            number: index + 1,
+            html,
+            highlighted: info.is_highlighted(index + 1),
+        })
+        .collect();
+
+    render_template(
         "codeblock",
         &CodeBlock {
-            lang: lang.unwrap_or(" ").to_string(),
-            contents: input.to_string(),
+            lang: info.lang.clone().unwrap_or_else(|| " ".to_string()),
+            title: info.title.clone(),
+            linenos: info.linenos,
+            lines,
         },
     )
+    .map_err(|source| Error::Template {
+        template: "codeblock",
+        offset,
+        source,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fence_info_empty() {
+        let info = FenceInfo::parse("");
+        assert_eq!(info.lang, None);
+        assert_eq!(info.title, None);
+        assert!(info.highlight.is_empty());
+        assert!(!info.linenos);
+    }
+
+    #[test]
+    fn fence_info_lang_only() {
+        let info = FenceInfo::parse("rust");
+        assert_eq!(info.lang.as_deref(), Some("rust"));
+        assert_eq!(info.title, None);
+    }
+
+    #[test]
+    fn fence_info_quoted_title() {
+        let info = FenceInfo::parse(r#"rust title="src/main file.rs""#);
+        assert_eq!(info.lang.as_deref(), Some("rust"));
+        assert_eq!(info.title.as_deref(), Some("src/main file.rs"));
+    }
+
+    #[test]
+    fn fence_info_unquoted_title() {
+        let info = FenceInfo::parse("yaml title=compose.yml");
+        assert_eq!(info.title.as_deref(), Some("compose.yml"));
+    }
+
+    #[test]
+    fn fence_info_options_in_any_order() {
+        let first = FenceInfo::parse(r#"rust {3} linenos title="a b""#);
+        let second = FenceInfo::parse(r#"rust title="a b" linenos {3}"#);
+        for info in [first, second] {
+            assert_eq!(info.lang.as_deref(), Some("rust"));
+            assert_eq!(info.title.as_deref(), Some("a b"));
+            assert_eq!(info.highlight, vec![3..=3]);
+            assert!(info.linenos);
+        }
+    }
+
+    #[test]
+    fn fence_info_missing_lang() {
+        let info = FenceInfo::parse(r#"{2} title="x.sh" linenos"#);
+        assert_eq!(info.lang, None);
+        assert_eq!(info.title.as_deref(), Some("x.sh"));
+        assert_eq!(info.highlight, vec![2..=2]);
+        assert!(info.linenos);
+    }
+
+    #[test]
+    fn fence_info_only_first_token_is_lang() {
+        let info = FenceInfo::parse("linenos rust");
+        assert_eq!(info.lang, None);
+        assert!(info.linenos);
+    }
+
+    #[test]
+    fn fence_info_line_ranges() {
+        let info = FenceInfo::parse("rust {3,5-7}");
+        assert_eq!(info.highlight, vec![3..=3, 5..=7]);
+        assert!(info.is_highlighted(3));
+        assert!(!info.is_highlighted(4));
+        assert!(info.is_highlighted(6));
+        assert!(!info.is_highlighted(8));
+    }
+
+    #[test]
+    fn fence_info_line_ranges_with_spaces() {
+        let info = FenceInfo::parse("rust { 3, 5 - 7 } linenos");
+        assert_eq!(info.highlight, vec![3..=3, 5..=7]);
+        assert!(info.linenos);
+    }
+
+    #[test]
+    fn fence_info_reversed_range() {
+        let info = FenceInfo::parse("rust {7-5}");
+        assert_eq!(info.highlight, vec![5..=7]);
+    }
+
+    #[test]
+    fn fence_info_skips_invalid_ranges() {
+        let info = FenceInfo::parse("rust {a,3,-,4-b}");
+        assert_eq!(info.highlight, vec![3..=3]);
+    }
+
+    #[test]
+    fn fence_info_quotes_keep_braces() {
+        let info = FenceInfo::parse(r#"rust title="{a} b" {2}"#);
+        assert_eq!(info.title.as_deref(), Some("{a} b"));
+        assert_eq!(info.highlight, vec![2..=2]);
+    }
+
+    #[test]
+    fn split_fence_info_tokens() {
+        assert_eq!(
+            split_fence_info(r#"  rust   title="a  b"  {1, 2}  "#),
+            vec!["rust", r#"title="a  b""#, "{1, 2}"]
+        );
+    }
+
+    #[test]
+    fn parse_line_range_values() {
+        assert_eq!(parse_line_range("3"), Some(3..=3));
+        assert_eq!(parse_line_range(" 5-7 "), Some(5..=7));
+        assert_eq!(parse_line_range("7-5"), Some(5..=7));
+        assert_eq!(parse_line_range(""), None);
+        assert_eq!(parse_line_range("x"), None);
+        assert_eq!(parse_line_range("1-"), None);
+    }
+
+    /// Parses `markdown`, which has to be a single blockquote, as a callout.
+    fn callout(markdown: &str) -> Option<BlockQuote> {
+        let mut events: Vec<_> = pulldown_cmark::Parser::new(markdown)
+            .into_offset_iter()
+            .collect();
+        assert!(matches!(
+            events.first(),
+            Some((Event::Start(Tag::BlockQuote(_)), _))
+        ));
+        events.remove(0);
+        events.pop();
+        parse_callout(&events)
+    }
+
+    #[test]
+    fn callout_folded_with_title() {
+        let callout = callout("> [!tip]- Title\n> Body").unwrap();
+        assert!(matches!(callout.blockquote_type, BlockquoteTypes::Tip));
+        assert!(callout.foldable);
+        assert!(!callout.open);
+        assert_eq!(callout.title.trim(), "Title");
+        assert_eq!(callout.contents.trim(), "<p>Body</p>");
+    }
+
+    #[test]
+    fn callout_foldable_open() {
+        let callout = callout("> [!question]+ Why?\n> Because").unwrap();
+        assert!(matches!(callout.blockquote_type, BlockquoteTypes::Question));
+        assert!(callout.foldable);
+        assert!(callout.open);
+        assert_eq!(callout.title.trim(), "Why?");
+    }
+
+    #[test]
+    fn callout_marker_without_title() {
+        let callout = callout("> [!warning]\n> Careful").unwrap();
+        assert!(matches!(callout.blockquote_type, BlockquoteTypes::Warning));
+        assert!(!callout.foldable);
+        assert!(callout.open);
+        assert_eq!(callout.title, "Warning");
+        assert_eq!(callout.contents.trim(), "<p>Careful</p>");
+    }
+
+    #[test]
+    fn callout_title_and_body_in_one_paragraph() {
+        let callout = callout("> [!note] My title\n> first line\n> second line").unwrap();
+        assert_eq!(callout.title.trim(), "My title");
+        assert_eq!(callout.contents.trim(), "<p>first line\nsecond line</p>");
+    }
+
+    #[test]
+    fn callout_body_in_later_paragraphs() {
+        let callout = callout("> [!info] Title\n>\n> First\n>\n> Second").unwrap();
+        assert_eq!(callout.title.trim(), "Title");
+        assert_eq!(callout.contents.trim(), "<p>First</p>\n<p>Second</p>");
+    }
+
+    #[test]
+    fn callout_title_keeps_inline_markdown() {
+        let callout = callout("> [!example] A **bold** title").unwrap();
+        assert_eq!(callout.title.trim(), "A <strong>bold</strong> title");
+        assert!(callout.contents.is_empty());
+    }
+
+    #[test]
+    fn callout_marker_is_case_insensitive() {
+        let callout = callout("> [!FAQ] Title").unwrap();
+        assert!(matches!(callout.blockquote_type, BlockquoteTypes::Question));
+    }
+
+    #[test]
+    fn callout_unknown_type_falls_back_to_note() {
+        let callout = callout("> [!unknownthing]\n> Body").unwrap();
+        assert!(matches!(callout.blockquote_type, BlockquoteTypes::Note));
+        assert_eq!(callout.title, "Unknownthing");
+        assert!(!is_known_callout("unknownthing"));
+    }
+
+    #[test]
+    fn blockquote_without_marker_isnt_a_callout() {
+        assert!(callout("> Just a quote").is_none());
+        assert!(callout("> [not a marker]").is_none());
+        assert!(callout("> - [!tip] in a list").is_none());
+    }
+
+    #[test]
+    fn nested_callouts() {
+        let templates =
+            std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("../../assets/templates");
+        shared_utils::load_templates(&templates, false).unwrap();
+
+        let markdown = "> [!note] Outer\n> > [!warning] Inner\n> > Inner body\n>\n> Outer body";
+        let parser = pulldown_cmark::Parser::new(markdown).into_offset_iter();
+        let diagnostics = Diagnostics::default();
+        let events: Vec<_> = format_blockquotes(parser, &diagnostics).collect();
+
+        let [(Event::Html(html), _)] = events.as_slice() else {
+            panic!("expected a single callout, got {events:?}");
+        };
+        let outer = html.find("callout-note").unwrap();
+        let inner = html.find("callout-warning").unwrap();
+        assert!(outer < inner);
+        assert!(html.contains("Inner body"));
+        assert!(html.contains("Outer body"));
+        assert!(!html.contains("[!warning]"));
+        assert!(diagnostics.into_parts().0.is_empty());
+    }
+
+    #[test]
+    fn image_embed_without_pothole() {
+        assert_eq!(
+            format_image_embed("cat.png", ""),
+            r#"<img src="/images/cat.png" alt="cat" />"#
+        );
+    }
+
+    #[test]
+    fn image_embed_width() {
+        assert_eq!(
+            format_image_embed("cat.png", "300"),
+            r#"<img src="/images/cat.png" alt="cat" width="300" />"#
+        );
+    }
+
+    #[test]
+    fn image_embed_width_and_height() {
+        assert_eq!(
+            format_image_embed("cat.png", "300x200"),
+            r#"<img src="/images/cat.png" alt="cat" width="300" height="200" />"#
+        );
+    }
+
+    #[test]
+    fn image_embed_caption() {
+        assert_eq!(
+            format_image_embed("cat.png", "A sleepy cat"),
+            r#"<img src="/images/cat.png" alt="A sleepy cat" />"#
+        );
+    }
+
+    #[test]
+    fn image_embed_caption_and_size() {
+        assert_eq!(
+            format_image_embed("cat.png", "A sleepy cat|300x200"),
+            r#"<img src="/images/cat.png" alt="A sleepy cat" width="300" height="200" />"#
+        );
+    }
+
+    #[test]
+    fn image_embed_blank_caption_uses_file_name() {
+        assert_eq!(
+            format_image_embed("cat.png", "  |300"),
+            r#"<img src="/images/cat.png" alt="cat" width="300" />"#
+        );
+    }
+
+    #[test]
+    fn image_embed_invalid_size_is_ignored() {
+        assert_eq!(
+            format_image_embed("cat.png", "A cat|big"),
+            r#"<img src="/images/cat.png" alt="A cat" />"#
+        );
+    }
+
+    #[test]
+    fn image_embed_percent_encodes_file_name() {
+        assert_eq!(
+            format_image_embed("my cat #1?.png", ""),
+            r#"<img src="/images/my%20cat%20%231%3F.png" alt="my cat #1?" />"#
+        );
+    }
+
+    #[test]
+    fn image_embed_escapes_alt() {
+        assert_eq!(
+            format_image_embed("cat.png", r#"a "b" <c> & d"#),
+            r#"<img src="/images/cat.png" alt="a &quot;b&quot; &lt;c&gt; &amp; d" />"#
+        );
+    }
+
+    #[test]
+    fn parse_image_size_values() {
+        assert_eq!(parse_image_size("300"), Some((300, None)));
+        assert_eq!(parse_image_size(" 300x200 "), Some((300, Some(200))));
+        assert_eq!(parse_image_size(""), None);
+        assert_eq!(parse_image_size("big"), None);
+        assert_eq!(parse_image_size("x200"), None);
+        assert_eq!(parse_image_size("300x"), None);
+        assert_eq!(parse_image_size("-300"), None);
+    }
+
+    #[test]
+    fn embed_images_only_replaces_image_embeds() {
+        let mut options = pulldown_cmark::Options::empty();
+        options.insert(pulldown_cmark::Options::ENABLE_WIKILINKS);
+        let markdown = "![[cat.png|A cat|300]] ![[Some note]]";
+        let parser = pulldown_cmark::Parser::new_ext(markdown, options).into_offset_iter();
+
+        let mut html = String::new();
+        pulldown_cmark::html::push_html(&mut html, embed_images(parser).map(|(event, _)| event));
+        assert_eq!(
+            html.trim(),
+            r#"<p><img src="/images/cat.png" alt="A cat" width="300" /> <img src="Some%20note" alt="Some note" /></p>"#
+        );
+    }
 }
@@ -1,9 +1,78 @@
+use std::{convert::Infallible, path::PathBuf};
+
 use actix_files as fs;
-use actix_web::{App, HttpServer};
+use actix_web::{
+    App, Error, HttpResponse, HttpServer,
+    body::{MessageBody, to_bytes},
+    dev::{ServiceRequest, ServiceResponse},
+    http::{
+        StatusCode,
+        header::{CACHE_CONTROL, CONTENT_TYPE},
+    },
+    middleware::{Condition, Next, from_fn},
+    web::{self, Bytes},
+};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::debug;
+
+/// The route browsers listen on for reload events.
+const LIVE_RELOAD_ROUTE: &str = "/__livereload";
+
+/// Injected into every html page when live reloading is enabled.
+const LIVE_RELOAD_SCRIPT: &str =
+    r#"<script>new EventSource("/__livereload").onmessage = () => location.reload();</script>"#;
+
+/// A handle used to tell every open browser tab to reload.
+#[derive(Clone)]
+pub struct LiveReload {
+    sender: broadcast::Sender<()>,
+}
+
+impl Default for LiveReload {
+    fn default() -> Self {
+        let (sender, _) = broadcast::channel(16);
+        Self { sender }
+    }
+}
+
+impl LiveReload {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sends a reload event to every connected page.
+    pub fn reload(&self) {
+        // An error only means no pages are currently open
+        let connected = self.sender.send(()).unwrap_or(0);
+        debug!("sent reload to {connected} pages");
+    }
+}
+
+/// Serves the contents of `output_dir` on <http://127.0.0.1:8080>.
+///
+/// When `live_reload` is set a script is injected into every page which
+/// reloads it whenever [`LiveReload::reload`] is called.
+///
+/// # Errors
+/// - Binding to the port
+pub async fn start_file_server(
+    output_dir: PathBuf,
+    live_reload: Option<LiveReload>,
+) -> Result<(), std::io::Error> {
+    HttpServer::new(move || {
+        let mut app = App::new().wrap(Condition::new(
+            live_reload.is_some(),
+            from_fn(inject_reload_script),
+        ));
 
-pub async fn start_file_server() -> Result<(), std::io::Error> {
-    HttpServer::new(|| {
-        App::new().service(fs::Files::new("/", "./output").index_file("index.html"))
+        if let Some(live_reload) = &live_reload {
+            app = app
+                .app_data(web::Data::new(live_reload.clone()))
+                .route(LIVE_RELOAD_ROUTE, web::get().to(reload_events));
+        }
+
+        app.service(fs::Files::new("/", &output_dir).index_file("index.html"))
     })
     .bind(("127.0.0.1", 8080))?
     .run()
@@ -11,3 +80,56 @@ pub async fn start_file_server() -> Result<(), std::io::Error> {
 
     Ok(())
 }
+
+/// A server-sent event stream which emits a message on every reload.
+async fn reload_events(live_reload: web::Data<LiveReload>) -> HttpResponse {
+    let receiver = live_reload.sender.subscribe();
+    let events = futures_util::stream::unfold(receiver, |mut receiver| async move {
+        loop {
+            match receiver.recv().await {
+                Ok(()) => {
+                    let event = Bytes::from_static(b"data: reload\n\n");
+                    return Some((Ok::<_, Infallible>(event), receiver));
+                }
+                Err(RecvError::Lagged(_)) => {}
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header((CACHE_CONTROL, "no-cache"))
+        .streaming(events)
+}
+
+async fn inject_reload_script(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let res = next.call(req).await?;
+
+    let is_html = res
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("text/html"));
+    if res.status() != StatusCode::OK || !is_html {
+        return Ok(res.map_into_left_body());
+    }
+
+    let (req, res) = res.into_parts();
+    let (res, body) = res.into_parts();
+    let body = to_bytes(body)
+        .await
+        .map_err(|err| actix_web::error::ErrorInternalServerError(err.into()))?;
+
+    let mut html = String::from_utf8_lossy(&body).into_owned();
+    match html.rfind("</body>") {
+        Some(index) => html.insert_str(index, LIVE_RELOAD_SCRIPT),
+        None => html.push_str(LIVE_RELOAD_SCRIPT),
+    }
+
+    let res = res.set_body(html);
+    Ok(ServiceResponse::new(req, res).map_into_right_body())
+}
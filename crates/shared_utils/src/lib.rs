@@ -1,34 +1,115 @@
-use std::sync::LazyLock;
+use std::{
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
 
 use handlebars::Handlebars;
+use serde::Serialize;
+use thiserror::Error;
 
-pub static HANDLEBARS: LazyLock<Handlebars<'static>> = LazyLock::new(|| {
-    let mut handlebars = handlebars::Handlebars::new();
-
-    // Register partials
-    handlebars
-        .register_template_file("navbar", "./assets/templates/navbar.html")
-        .unwrap();
-    handlebars
-        .register_template_file("styles", "./assets/templates/styles.html")
-        .unwrap();
-
-    // Register templates
-    handlebars
-        .register_template_file("blog", "./assets/templates/blog.html")
-        .unwrap();
-    handlebars
-        .register_template_file("homepage", "./assets/templates/homepage.html")
-        .unwrap();
-    handlebars
-        .register_template_file("blockquote", "./assets/templates/modules/blockquote.html")
-        .unwrap();
-    handlebars
-        .register_template_file("codeblock", "./assets/templates/modules/codeblock.html")
-        .unwrap();
-    handlebars
-        .register_template_file("tag_page", "./assets/templates/tag_page.html")
-        .unwrap();
-
-    handlebars
-});
+/// Every template, with its name and path relative to the template directory.
+const TEMPLATES: &[(&str, &str)] = &[
+    // Partials
+    ("navbar", "navbar.html"),
+    ("styles", "styles.html"),
+    ("feeds", "feeds.html"),
+    ("pagination", "modules/pagination.html"),
+    // Templates
+    ("blog", "blog.html"),
+    ("homepage", "homepage.html"),
+    ("blockquote", "modules/blockquote.html"),
+    ("codeblock", "modules/codeblock.html"),
+    ("tag_page", "tag_page.html"),
+    ("tag_index", "tag_index.html"),
+    ("archive", "archive.html"),
+];
+
+static TEMPLATE_DIR: OnceLock<PathBuf> = OnceLock::new();
+static HANDLEBARS: OnceLock<Handlebars<'static>> = OnceLock::new();
+
+#[derive(Debug, Error)]
+pub enum TemplateError {
+    #[error("templates were used before being loaded")]
+    NotLoaded,
+    #[error("failed to load template `{name}` from {}", path.display())]
+    Load {
+        name: &'static str,
+        path: PathBuf,
+        #[source]
+        source: Box<handlebars::TemplateError>,
+    },
+    #[error("failed to render template `{name}`")]
+    Render {
+        name: String,
+        #[source]
+        source: Box<handlebars::RenderError>,
+    },
+}
+
+impl TemplateError {
+    /// The template file the error happened in, with the 1-based line and column.
+    #[must_use]
+    pub fn location(&self) -> Option<(PathBuf, usize, usize)> {
+        match self {
+            Self::NotLoaded => None,
+            Self::Load { path, source, .. } => {
+                let (line, column) = source.pos()?;
+                Some((path.clone(), line, column))
+            }
+            Self::Render { name, source } => Some((
+                template_path(source.template_name.as_deref().unwrap_or(name))?,
+                source.line_no?,
+                source.column_no?,
+            )),
+        }
+    }
+}
+
+fn template_path(name: &str) -> Option<PathBuf> {
+    let (_, file) = TEMPLATES.iter().find(|(template, _)| *template == name)?;
+    Some(TEMPLATE_DIR.get()?.join(file))
+}
+
+/// Registers every template in `dir`, this has to be called once before [`render_template`].
+///
+/// In `dev_mode` template files are reread on every render, so edits show up without restarting.
+///
+/// # Errors
+/// - A template file is missing or has invalid syntax
+pub fn load_templates(dir: &Path, dev_mode: bool) -> Result<(), TemplateError> {
+    let mut handlebars = Handlebars::new();
+    handlebars.set_dev_mode(dev_mode);
+
+    for (name, file) in TEMPLATES {
+        let path = dir.join(file);
+        handlebars
+            .register_template_file(name, &path)
+            .map_err(|source| TemplateError::Load {
+                name,
+                path,
+                source: Box::new(source),
+            })?;
+    }
+
+    if TEMPLATE_DIR.set(dir.to_path_buf()).is_err() || HANDLEBARS.set(handlebars).is_err() {
+        tracing::warn!("templates were already loaded");
+    }
+
+    Ok(())
+}
+
+/// Renders the template called `name` with `data`.
+///
+/// # Errors
+/// - [`load_templates`] wasnt called
+/// - Rendering the template failed
+pub fn render_template<T: Serialize>(name: &str, data: &T) -> Result<String, TemplateError> {
+    HANDLEBARS
+        .get()
+        .ok_or(TemplateError::NotLoaded)?
+        .render(name, data)
+        .map_err(|source| TemplateError::Render {
+            name: name.to_string(),
+            source: Box::new(source),
+        })
+}